@@ -0,0 +1,148 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::YakShopError;
+
+/// Maps the days since the shop opened to calendar dates, e.g.:
+///
+/// ```toml
+/// [calendar]
+/// start_date = "2026-10-01"
+/// ```
+///
+/// Day 0 is the start date itself. Without a start date, only day numbers can be used.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calendar {
+    pub start_date: Option<NaiveDate>,
+}
+
+/// A day in the shop, given either as the number of days since the shop opened or as an ISO
+/// calendar date (`2026-11-01`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShopDay {
+    Number(u32),
+    Date(NaiveDate),
+}
+
+impl Calendar {
+    /// The date of the given day, or `None` if the calendar has no start date.
+    #[must_use]
+    pub fn date(&self, day: u32) -> Option<NaiveDate> {
+        self.start_date
+            .and_then(|start| start.checked_add_days(chrono::Days::new(u64::from(day))))
+    }
+
+    /// The number of days since the shop opened on the given day.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if a date is given without a start date, or the
+    /// date is before the shop opened.
+    pub fn day(&self, day: ShopDay) -> Result<u32, YakShopError> {
+        let date = match day {
+            ShopDay::Number(day) => return Ok(day),
+            ShopDay::Date(date) => date,
+        };
+
+        let Some(start) = self.start_date else {
+            return Err(YakShopError::InvalidParameter(format!(
+                "{date} can't be used without a calendar start date"
+            )));
+        };
+
+        let days = (date - start).num_days();
+        if days < 0 {
+            return Err(YakShopError::InvalidParameter(format!(
+                "{date} is before the shop opened on {start}"
+            )));
+        }
+
+        u32::try_from(days)
+            .map_err(|_| YakShopError::InvalidParameter(format!("{date} is too far in the future")))
+    }
+}
+
+impl Display for ShopDay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShopDay::Number(day) => write!(f, "{day}"),
+            ShopDay::Date(date) => write!(f, "{date}"),
+        }
+    }
+}
+
+impl FromStr for ShopDay {
+    type Err = YakShopError;
+
+    fn from_str(day: &str) -> Result<Self, Self::Err> {
+        if let Ok(day) = day.parse() {
+            return Ok(ShopDay::Number(day));
+        }
+
+        NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map(ShopDay::Date)
+            .map_err(|_| {
+                YakShopError::InvalidParameter(format!(
+                    "{day} is neither a day number nor an ISO date (YYYY-MM-DD)"
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn calendar() -> Calendar {
+        Calendar {
+            start_date: Some(date("2026-10-01")),
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("13".parse::<ShopDay>().unwrap(), ShopDay::Number(13));
+        assert_eq!(
+            "2026-11-01".parse::<ShopDay>().unwrap(),
+            ShopDay::Date(date("2026-11-01"))
+        );
+        assert!(matches!(
+            "2026-13-01".parse::<ShopDay>(),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+        assert!("-1".parse::<ShopDay>().is_err());
+    }
+
+    #[test]
+    fn test_day() {
+        let calendar = calendar();
+        assert_eq!(calendar.day(ShopDay::Number(13)).unwrap(), 13);
+        assert_eq!(calendar.day(ShopDay::Date(date("2026-10-01"))).unwrap(), 0);
+        assert_eq!(calendar.day(ShopDay::Date(date("2026-11-01"))).unwrap(), 31);
+        assert!(calendar.day(ShopDay::Date(date("2026-09-30"))).is_err());
+    }
+
+    #[test]
+    fn test_day_without_start_date() {
+        let calendar = Calendar::default();
+        assert_eq!(calendar.day(ShopDay::Number(13)).unwrap(), 13);
+        assert!(matches!(
+            calendar.day(ShopDay::Date(date("2026-11-01"))),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(calendar().date(0), Some(date("2026-10-01")));
+        assert_eq!(calendar().date(31), Some(date("2026-11-01")));
+        assert_eq!(Calendar::default().date(31), None);
+    }
+}
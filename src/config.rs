@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Calendar, CostConfig, Prices, Quantity, Season, Seasons, YakShopError};
+
+/// The configuration file looked for in the working directory when none is given explicitly.
+pub const DEFAULT_CONFIG_FILE: &str = "yakshop.toml";
+
+/// The largest daily milk production allowed, which (even with the largest seasonal multiplier)
+/// keeps the lifetime production of a single yak well within the range of a `Quantity`.
+const MAX_BASE_MILK_PRODUCTION: Quantity = Quantity::from_whole(100_000);
+
+/// The configuration of a shop, as read from a `yakshop.toml` file, e.g.:
+///
+/// ```toml
+/// [farm]
+/// days_in_yak_year = 100
+/// max_yak_age = 1000
+///
+/// [calendar]
+/// start_date = "2026-10-01"
+///
+/// [[seasons]]
+/// name = "winter"
+/// months = [12, 1, 2]
+/// milk = 0.7
+///
+/// [costs]
+/// feed = 1.5
+///
+/// [prices]
+/// milk = 1.2
+/// ```
+///
+/// Every setting is optional and falls back to the values from the original challenge.
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShopConfig {
+    pub farm: FarmConfig,
+    pub calendar: Calendar,
+    pub seasons: Vec<Season>,
+    pub costs: CostConfig,
+    pub prices: Prices,
+}
+
+/// The parameters which determine how yaks age and produce.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FarmConfig {
+    /// The number of days in a yak year, used to convert the ages in herd files.
+    pub days_in_yak_year: u32,
+    /// The age (in days) at which a yak dies.
+    pub max_yak_age: u32,
+    /// The liters of milk a newborn yak produces a day.
+    pub base_milk_production: Quantity,
+    /// The liters by which the daily milk production drops for every day of age.
+    pub milk_production_decline: Quantity,
+    /// The age (in days) from which a yak can be shaved.
+    pub min_shave_age: u32,
+    /// The minimum number of days between two shaves, before the 1% of age added on top.
+    pub shave_period: u32,
+}
+
+impl Default for FarmConfig {
+    fn default() -> Self {
+        FarmConfig {
+            days_in_yak_year: 100,
+            max_yak_age: 1_000,
+            base_milk_production: Quantity::from_whole(50),
+            milk_production_decline: Quantity::from_thousandths(30),
+            min_shave_age: 100,
+            shave_period: 8,
+        }
+    }
+}
+
+impl FarmConfig {
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` naming the first setting out of range.
+    pub fn validate(&self) -> Result<(), YakShopError> {
+        if self.days_in_yak_year == 0 {
+            return Err(invalid_setting("days_in_yak_year must be greater than 0"));
+        }
+        if self.max_yak_age == 0 {
+            return Err(invalid_setting("max_yak_age must be greater than 0"));
+        }
+        if self.base_milk_production > MAX_BASE_MILK_PRODUCTION {
+            return Err(invalid_setting(&format!(
+                "base_milk_production must be at most {MAX_BASE_MILK_PRODUCTION}"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings in a herd file which take precedence over the shop configuration for that herd only.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FarmOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days_in_yak_year: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_yak_age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_milk_production: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milk_production_decline: Option<Quantity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_shave_age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shave_period: Option<u32>,
+}
+
+impl FarmOverrides {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == FarmOverrides::default()
+    }
+
+    /// Applies the overrides to `farm`, validating the result.
+    pub(crate) fn apply(&self, farm: &FarmConfig) -> Result<FarmConfig, YakShopError> {
+        let farm = FarmConfig {
+            days_in_yak_year: self.days_in_yak_year.unwrap_or(farm.days_in_yak_year),
+            max_yak_age: self.max_yak_age.unwrap_or(farm.max_yak_age),
+            base_milk_production: self
+                .base_milk_production
+                .unwrap_or(farm.base_milk_production),
+            milk_production_decline: self
+                .milk_production_decline
+                .unwrap_or(farm.milk_production_decline),
+            min_shave_age: self.min_shave_age.unwrap_or(farm.min_shave_age),
+            shave_period: self.shave_period.unwrap_or(farm.shave_period),
+        };
+
+        farm.validate()?;
+        Ok(farm)
+    }
+
+    /// The overrides as attributes of a `farm` element of a herd.xml file, or `None` if there are
+    /// no overrides.
+    pub(crate) fn to_xml(self) -> Option<String> {
+        let attributes: Vec<String> = [
+            (
+                "days_in_yak_year",
+                self.days_in_yak_year.map(|v| v.to_string()),
+            ),
+            ("max_yak_age", self.max_yak_age.map(|v| v.to_string())),
+            (
+                "base_milk_production",
+                self.base_milk_production.map(|v| v.to_string()),
+            ),
+            (
+                "milk_production_decline",
+                self.milk_production_decline.map(|v| v.to_string()),
+            ),
+            ("min_shave_age", self.min_shave_age.map(|v| v.to_string())),
+            ("shave_period", self.shave_period.map(|v| v.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!(r#"{name}="{value}""#)))
+        .collect();
+
+        if attributes.is_empty() {
+            None
+        } else {
+            Some(format!("<farm {}/>", attributes.join(" ")))
+        }
+    }
+}
+
+impl FromStr for ShopConfig {
+    type Err = YakShopError;
+
+    /// Parses and validates a configuration in TOML.
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        let config: ShopConfig = toml::from_str(config)
+            .map_err(|err| YakShopError::ConfigFileParseError(format!("{err:?}")))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl ShopConfig {
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` naming the first setting or season out of range.
+    pub fn validate(&self) -> Result<(), YakShopError> {
+        self.farm.validate()?;
+        self.resolve_seasons().map(|_| ())
+    }
+
+    /// The seasons resolved against the calendar and farm of this configuration.
+    ///
+    /// # Errors
+    ///
+    /// See `Seasons::new`.
+    pub fn resolve_seasons(&self) -> Result<Seasons, YakShopError> {
+        Seasons::new(&self.seasons, self.calendar, &self.farm)
+    }
+
+    /// Loads the configuration file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::ConfigFileNotFound` if the file does not exist,
+    /// `YakShopError::ConfigFileReadError` if it cannot be read,
+    /// `YakShopError::ConfigFileParseError` if it is not valid TOML, or
+    /// `YakShopError::InvalidParameter` if a setting is out of range.
+    pub fn from_path(path: &Path) -> Result<Self, YakShopError> {
+        if !path.exists() {
+            return Err(YakShopError::ConfigFileNotFound(path.to_path_buf()));
+        }
+
+        std::fs::read_to_string(path)
+            .map_err(YakShopError::ConfigFileReadError)?
+            .parse()
+    }
+
+    /// Loads the configuration from `path` if given, otherwise from `yakshop.toml` in the working
+    /// directory if it exists. Without either, the default configuration is used.
+    ///
+    /// # Errors
+    ///
+    /// See `ShopConfig::from_path`.
+    pub fn load(path: Option<&Path>) -> Result<Self, YakShopError> {
+        match path {
+            Some(path) => ShopConfig::from_path(path),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                ShopConfig::from_path(Path::new(DEFAULT_CONFIG_FILE))
+            }
+            None => Ok(ShopConfig::default()),
+        }
+    }
+}
+
+fn invalid_setting(msg: &str) -> YakShopError {
+    YakShopError::InvalidParameter(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_partial_config() {
+        let config: ShopConfig = "[farm]\ndays_in_yak_year = 365\nbase_milk_production = 42.5"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            config.farm,
+            FarmConfig {
+                days_in_yak_year: 365,
+                base_milk_production: "42.5".parse().unwrap(),
+                ..FarmConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_calendar_config() {
+        let config: ShopConfig = "[calendar]\nstart_date = \"2026-10-01\"".parse().unwrap();
+        assert_eq!(
+            config.calendar.start_date,
+            Some("2026-10-01".parse().unwrap())
+        );
+
+        let result = "[calendar]\nstart_date = \"01-10-2026\"".parse::<ShopConfig>();
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+
+    #[test]
+    fn test_seasons_config() {
+        let config: ShopConfig = "[[seasons]]\nname = \"summer\"\nstart_day = 50\nmilk = 1.2"
+            .parse()
+            .unwrap();
+        assert_eq!(config.seasons[0].milk, "1.2".parse().unwrap());
+        assert_eq!(config.seasons[0].wool, Quantity::from_whole(1));
+
+        // Months require a start date
+        let result = "[[seasons]]\nname = \"winter\"\nmonths = [12, 1, 2]".parse::<ShopConfig>();
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_costs_config() {
+        let config: ShopConfig = "[costs]\ncare = 0.75\n\n[prices]\nwool = 35"
+            .parse()
+            .unwrap();
+        assert_eq!(config.costs.care, "0.75".parse().unwrap());
+        assert_eq!(config.costs.feed, CostConfig::default().feed);
+        assert_eq!(config.prices.wool, Quantity::from_whole(35));
+    }
+
+    #[test]
+    fn test_empty_config() {
+        let config: ShopConfig = "".parse().unwrap();
+        assert_eq!(config, ShopConfig::default());
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        let result = "[farm]\ndays_in_a_year = 365".parse::<ShopConfig>();
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+
+    #[test]
+    fn test_invalid_setting() {
+        let result = "[farm]\ndays_in_yak_year = 0".parse::<ShopConfig>();
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+
+        let result = "[farm]\nbase_milk_production = 100001".parse::<ShopConfig>();
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_from_path() {
+        let config = ShopConfig::from_path(&PathBuf::from("./examples/yakshop.toml")).unwrap();
+        assert_eq!(config, ShopConfig::default());
+
+        let result = ShopConfig::from_path(&PathBuf::from("./missing.toml"));
+        assert!(matches!(result, Err(YakShopError::ConfigFileNotFound(_))));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = FarmOverrides {
+            max_yak_age: Some(2_000),
+            ..FarmOverrides::default()
+        };
+        let farm = overrides.apply(&FarmConfig::default()).unwrap();
+
+        assert_eq!(farm.max_yak_age, 2_000);
+        assert_eq!(farm.days_in_yak_year, 100);
+        assert_eq!(
+            overrides.to_xml().as_deref(),
+            Some(r#"<farm max_yak_age="2000"/>"#)
+        );
+        assert_eq!(FarmOverrides::default().to_xml(), None);
+
+        let invalid = FarmOverrides {
+            days_in_yak_year: Some(0),
+            ..FarmOverrides::default()
+        };
+        assert!(invalid.apply(&FarmConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{Calendar, HerdFormat, NaiveDate, Products, Shop, ShopConfig, ShopDay, YakShopError};
+
+/// Several shops which run side by side, such as the farms of a cooperative. Every shop is known by
+/// an id, which for shops loaded from a directory is the name of its herd file without the
+/// extension.
+#[derive(Default, Debug, Clone)]
+pub struct Cooperative {
+    shops: BTreeMap<String, Shop>,
+    /// The calendar all shops share, as the herd files can only override the farm settings
+    calendar: Calendar,
+}
+
+impl Cooperative {
+    /// Creates an empty cooperative.
+    #[must_use]
+    pub fn new(config: &ShopConfig) -> Self {
+        Cooperative {
+            shops: BTreeMap::new(),
+            calendar: config.calendar,
+        }
+    }
+
+    /// Loads every herd file in `dir` as a separate shop. Files without a herd file extension
+    /// (xml, json, yaml, yml or toml) are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::ConfigFileNotFound` if the directory does not exist,
+    /// `YakShopError::ConfigFileReadError` if it cannot be read, `YakShopError::InvalidParameter`
+    /// if it holds no herd files or two herd files with the same name, or any of the errors of
+    /// `Shop::from_path` for the herd files in it.
+    pub fn from_dir(dir: &Path, config: &ShopConfig) -> Result<Self, YakShopError> {
+        if !dir.exists() {
+            return Err(YakShopError::ConfigFileNotFound(dir.to_path_buf()));
+        }
+
+        let mut cooperative = Cooperative::new(config);
+
+        let mut paths = std::fs::read_dir(dir)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(YakShopError::ConfigFileReadError)?;
+        // Load in a fixed order, so the same error is reported every time
+        paths.sort();
+
+        for path in paths {
+            let Some(format) = HerdFormat::from_path(&path).filter(|_| path.is_file()) else {
+                continue;
+            };
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let shop = Shop::from_path(&path, Some(format), config)?;
+            cooperative.insert(id, shop)?;
+        }
+
+        if cooperative.shops.is_empty() {
+            return Err(YakShopError::InvalidParameter(format!(
+                "{} has no herd files",
+                dir.to_string_lossy()
+            )));
+        }
+
+        Ok(cooperative)
+    }
+
+    /// Adds a shop to the cooperative.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if there already is a shop with the same id.
+    pub fn insert(&mut self, id: &str, shop: Shop) -> Result<(), YakShopError> {
+        if self.shops.contains_key(id) {
+            return Err(YakShopError::InvalidParameter(format!(
+                "There is more than one shop named {id}"
+            )));
+        }
+
+        self.shops.insert(id.to_string(), shop);
+        Ok(())
+    }
+
+    /// The shops of the cooperative, by id.
+    #[must_use]
+    pub fn shops(&self) -> &BTreeMap<String, Shop> {
+        &self.shops
+    }
+
+    #[must_use]
+    pub fn shop(&self, id: &str) -> Option<&Shop> {
+        self.shops.get(id)
+    }
+
+    /// The number of days since the shops opened on `day`. See `Shop::day`.
+    ///
+    /// # Errors
+    ///
+    /// See `Calendar::day`.
+    pub fn day(&self, day: ShopDay) -> Result<u32, YakShopError> {
+        self.calendar.day(day)
+    }
+
+    /// The current date of the shops, if the calendar has a start date.
+    #[must_use]
+    pub fn date(&self) -> Option<NaiveDate> {
+        self.shops
+            .values()
+            .next()
+            .and_then(|shop| self.calendar.date(shop.elapsed_days))
+    }
+
+    /// Steps every shop forward by the given number of days, returning the products produced by
+    /// all of them in that time.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the stock of a shop or the cooperative no longer fits.
+    pub fn step_days(&mut self, days: u32) -> Result<Products, YakShopError> {
+        self.shops
+            .values_mut()
+            .try_fold(Products::default(), |produced, shop| {
+                produced.checked_add(&shop.step_days(days)?)
+            })
+    }
+
+    /// The stock of all shops together.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the total no longer fits.
+    pub fn produced_products(&self) -> Result<Products, YakShopError> {
+        self.shops
+            .values()
+            .try_fold(Products::default(), |produced, shop| {
+                produced.checked_add(&shop.produced_products)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quantity;
+    use std::path::PathBuf;
+
+    fn fixtures_path() -> PathBuf {
+        PathBuf::from("./tests/fixtures")
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let mut cooperative =
+            Cooperative::from_dir(&fixtures_path().join("cooperative"), &ShopConfig::default())
+                .unwrap();
+
+        let ids: Vec<&String> = cooperative.shops().keys().collect();
+        assert_eq!(ids, ["highlands", "valley"]);
+
+        cooperative.step_days(13).unwrap();
+
+        // The valley herd is the example herd, the highlands herd holds just Betty-1
+        assert_eq!(
+            cooperative.shop("valley").unwrap().produced_products.milk(),
+            "1104.48".parse::<Quantity>().unwrap()
+        );
+        assert_eq!(
+            cooperative.produced_products().unwrap(),
+            Products::new("1596.14".parse().unwrap(), 4)
+        );
+    }
+
+    #[test]
+    fn test_from_dir_invalid() {
+        let result =
+            Cooperative::from_dir(&fixtures_path().join("missing"), &ShopConfig::default());
+        assert!(matches!(result, Err(YakShopError::ConfigFileNotFound(_))));
+
+        // The fixtures start with a herd with an invalid age
+        let result = Cooperative::from_dir(&fixtures_path(), &ShopConfig::default());
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let mut cooperative = Cooperative::default();
+        cooperative.insert("valley", Shop::default()).unwrap();
+
+        let result = cooperative.insert("valley", Shop::default());
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+use crate::{FarmConfig, Products, Quantity, YakShopError};
+
+/// The daily cost of keeping a yak, e.g.:
+///
+/// ```toml
+/// [costs]
+/// feed = 1.5
+/// feed_per_year = 0.1
+/// care = 0.5
+/// ```
+///
+/// Older yaks eat more, so the daily feed cost grows by `feed_per_year` for every yak year of age.
+/// Yaks only cost money while they are alive.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostConfig {
+    /// The cost of feeding a newborn yak for a day.
+    pub feed: Quantity,
+    /// The extra daily feed cost for every yak year of age.
+    pub feed_per_year: Quantity,
+    /// The daily cost of shelter and veterinary care, regardless of age.
+    pub care: Quantity,
+}
+
+impl Default for CostConfig {
+    fn default() -> Self {
+        CostConfig {
+            feed: Quantity::from_thousandths(1_500),
+            feed_per_year: Quantity::from_thousandths(100),
+            care: Quantity::from_thousandths(500),
+        }
+    }
+}
+
+impl CostConfig {
+    /// The cost of keeping a yak which is `age` days old for the next `days` days.
+    pub(crate) fn upkeep(
+        &self,
+        farm: &FarmConfig,
+        age: u32,
+        days: u32,
+    ) -> Result<Expenses, YakShopError> {
+        let days = u128::from(days);
+        let days_in_yak_year = u128::from(farm.days_in_yak_year);
+
+        // The sum of the ages of the yak on each of the days, in days
+        let age_days = days * u128::from(age) + days * days.saturating_sub(1) / 2;
+        // Rounded to the nearest thousandth, with halves rounded up like `Quantity::checked_scale`
+        let age_feed = (u128::from(self.feed_per_year.thousandths()) * age_days
+            + days_in_yak_year / 2)
+            / days_in_yak_year;
+
+        let feed = days * u128::from(self.feed.thousandths()) + age_feed;
+        let care = days * u128::from(self.care.thousandths());
+
+        let to_quantity = |thousandths: u128, total: &str| {
+            u64::try_from(thousandths)
+                .map(Quantity::from_thousandths)
+                .map_err(|_| YakShopError::Overflow(total.to_string()))
+        };
+
+        Ok(Expenses {
+            feed: to_quantity(feed, "feed cost")?,
+            care: to_quantity(care, "care cost")?,
+        })
+    }
+}
+
+/// The prices the shop sells its products for, e.g.:
+///
+/// ```toml
+/// [prices]
+/// milk = 1.2
+/// wool = 40
+/// ```
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Prices {
+    /// The price of a liter of milk.
+    pub milk: Quantity,
+    /// The price of a skin of wool.
+    pub wool: Quantity,
+}
+
+impl Default for Prices {
+    fn default() -> Self {
+        Prices {
+            milk: Quantity::from_thousandths(1_200),
+            wool: Quantity::from_whole(40),
+        }
+    }
+}
+
+impl Prices {
+    /// The price of `products`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the price does not fit in a `Quantity`.
+    pub fn value(&self, products: &Products) -> Result<Quantity, YakShopError> {
+        let overflow = || YakShopError::Overflow("revenue".to_string());
+
+        let milk = products
+            .milk()
+            .checked_scale(self.milk)
+            .ok_or_else(overflow)?;
+        let wool = self
+            .wool
+            .thousandths()
+            .checked_mul(u64::from(products.wool()))
+            .map(Quantity::from_thousandths)
+            .ok_or_else(overflow)?;
+
+        milk.checked_add(wool).ok_or_else(overflow)
+    }
+}
+
+/// The costs of keeping the herd.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expenses {
+    pub feed: Quantity,
+    pub care: Quantity,
+}
+
+impl Expenses {
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` naming the cost which no longer fits.
+    pub fn checked_add(&self, other: &Expenses) -> Result<Expenses, YakShopError> {
+        Ok(Expenses {
+            feed: self
+                .feed
+                .checked_add(other.feed)
+                .ok_or_else(|| YakShopError::Overflow("feed cost".to_string()))?,
+            care: self
+                .care
+                .checked_add(other.care)
+                .ok_or_else(|| YakShopError::Overflow("care cost".to_string()))?,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the total does not fit in a `Quantity`.
+    pub fn total(&self) -> Result<Quantity, YakShopError> {
+        self.feed
+            .checked_add(self.care)
+            .ok_or_else(|| YakShopError::Overflow("cost".to_string()))
+    }
+}
+
+/// An order delivered by the shop.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub customer: String,
+    /// The day the order was delivered on
+    pub day: u32,
+    /// The products delivered, which may be less than the customer asked for
+    pub delivered: Products,
+}
+
+/// The profit and loss of a shop up to a day, see `Shop::profit_and_loss`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfitAndLoss {
+    /// The products sold by the orders delivered up to the day
+    pub sold: Products,
+    pub revenue: Quantity,
+    pub expenses: Expenses,
+}
+
+impl ProfitAndLoss {
+    /// The total of the costs.
+    #[must_use]
+    pub fn costs(&self) -> Quantity {
+        // SAFETY: `Shop::profit_and_loss` checks the total fits
+        self.expenses.total().unwrap_or(Quantity::ZERO)
+    }
+
+    /// The revenue minus the costs, negative for a loss. For presentation only.
+    #[must_use]
+    pub fn profit(&self) -> f64 {
+        let thousandths =
+            i128::from(self.revenue.thousandths()) - i128::from(self.costs().thousandths());
+
+        // SAFETY: Precision is only lost above 2^53 thousandths, see `Quantity::to_f64`
+        #[allow(clippy::cast_precision_loss)]
+        let profit = thousandths as f64 / 1_000.0;
+        profit
+    }
+}
+
+impl Display for ProfitAndLoss {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r"Profit and Loss:
+    {} liters of milk and {} skins of wool sold
+    Revenue: {}
+    Feed costs: {}
+    Care costs: {}
+",
+            self.sold.milk(),
+            self.sold.wool(),
+            self.revenue,
+            self.expenses.feed,
+            self.expenses.care,
+        )?;
+
+        let costs = self.costs();
+        if self.revenue >= costs {
+            write!(f, "    Profit: {}", self.revenue - costs)
+        } else {
+            write!(f, "    Loss: {}", costs - self.revenue)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_upkeep() {
+        let costs = CostConfig::default();
+        let farm = FarmConfig::default();
+
+        // A 4 year old yak eats 1.5 + 0.4 a day, rising by 0.001 a day
+        let expenses = costs.upkeep(&farm, 400, 13).unwrap();
+        assert_eq!(expenses.feed, amount("24.778"));
+        assert_eq!(expenses.care, amount("6.5"));
+
+        // The same as adding up each day
+        let mut total = Expenses::default();
+        for age in 400..413 {
+            total = total
+                .checked_add(&costs.upkeep(&farm, age, 1).unwrap())
+                .unwrap();
+        }
+        assert_eq!(total, expenses);
+
+        assert_eq!(costs.upkeep(&farm, 400, 0).unwrap(), Expenses::default());
+    }
+
+    #[test]
+    fn test_upkeep_overflow() {
+        let costs = CostConfig {
+            feed: Quantity::from_thousandths(u64::MAX),
+            ..CostConfig::default()
+        };
+
+        let result = costs.upkeep(&FarmConfig::default(), 0, 2);
+        assert!(matches!(result, Err(YakShopError::Overflow(_))));
+    }
+
+    #[test]
+    fn test_value() {
+        let prices = Prices::default();
+        let products = Products::new(amount("1104.48"), 3);
+        assert_eq!(prices.value(&products).unwrap(), amount("1445.376"));
+
+        let prices = Prices {
+            wool: Quantity::from_thousandths(u64::MAX),
+            ..Prices::default()
+        };
+        assert!(matches!(
+            prices.value(&products),
+            Err(YakShopError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let pnl = ProfitAndLoss {
+            sold: Products::new(amount("10"), 0),
+            revenue: amount("12"),
+            expenses: Expenses {
+                feed: amount("10"),
+                care: amount("5"),
+            },
+        };
+        assert_ulps_eq!(pnl.profit(), -3.0);
+        assert_eq!(
+            pnl.to_string(),
+            r"Profit and Loss:
+    10.000 liters of milk and 0 skins of wool sold
+    Revenue: 12.000
+    Feed costs: 10.000
+    Care costs: 5.000
+    Loss: 3.000"
+        );
+    }
+}
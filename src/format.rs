@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::FarmOverrides;
+use crate::yak::YakRecord;
+use crate::{Shop, ShopConfig, Yak, YakShopError};
+
+/// The file formats a herd can be described in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HerdFormat {
+    Xml,
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// The shape of a herd in every format except XML, e.g. in JSON:
+///
+/// `{ "farm": { "max_yak_age": 1200 }, "herd": [{ "name": "Betty-1", "age": 4, "sex": "f" }] }`
+#[derive(Deserialize, Serialize)]
+struct Herd {
+    #[serde(default, skip_serializing_if = "FarmOverrides::is_empty")]
+    farm: FarmOverrides,
+    herd: Vec<YakRecord>,
+}
+
+/// The shape of a herd.xml file, where the optional `farm` element must come first:
+///
+/// `<herd><farm max_yak_age="1200"/><labyak name="Betty-1" age="4" sex="f"/></herd>`
+#[derive(Deserialize)]
+struct XmlHerd {
+    #[serde(default)]
+    farm: FarmOverrides,
+    #[serde(default, rename = "labyak")]
+    yaks: Vec<YakRecord>,
+}
+
+impl Display for HerdFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HerdFormat::Xml => "xml",
+            HerdFormat::Json => "json",
+            HerdFormat::Yaml => "yaml",
+            HerdFormat::Toml => "toml",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for HerdFormat {
+    type Err = YakShopError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "xml" => Ok(HerdFormat::Xml),
+            "json" => Ok(HerdFormat::Json),
+            "yaml" | "yml" => Ok(HerdFormat::Yaml),
+            "toml" => Ok(HerdFormat::Toml),
+            _ => Err(YakShopError::InvalidParameter(format!(
+                "Unknown herd format {format}, expected one of xml, json, yaml or toml"
+            ))),
+        }
+    }
+}
+
+impl HerdFormat {
+    /// Determines the format from the extension of `path`, if it has a known one.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+    }
+
+    /// Guesses the format from the content of a herd file.
+    #[must_use]
+    pub fn detect(content: &str) -> Self {
+        let content = content.trim_start();
+
+        if content.starts_with('<') {
+            HerdFormat::Xml
+        } else if content.starts_with('{')
+            || (content.starts_with('[') && !content.starts_with("[["))
+        {
+            HerdFormat::Json
+        } else if content.starts_with("[[")
+            || content
+                .lines()
+                .next()
+                .is_some_and(|line| line.contains('=') && !line.contains(':'))
+        {
+            HerdFormat::Toml
+        } else {
+            HerdFormat::Yaml
+        }
+    }
+
+    /// Parses a herd described in this format, applying any `farm` settings in the herd on top of
+    /// `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::ConfigFileParseError` if the content is not a valid herd, or
+    /// `YakShopError::InvalidParameter` if its farm settings are out of range.
+    pub fn parse(self, content: &str, config: &ShopConfig) -> Result<Shop, YakShopError> {
+        // TODO: These serde error messages could be a lot nicer
+        let herd = match self {
+            HerdFormat::Xml => serde_xml_rs::from_str::<XmlHerd>(content)
+                .map(|herd| Herd {
+                    farm: herd.farm,
+                    herd: herd.yaks,
+                })
+                .map_err(parse_error)?,
+            HerdFormat::Json => serde_json::from_str(content).map_err(parse_error)?,
+            HerdFormat::Yaml => serde_yaml::from_str(content).map_err(parse_error)?,
+            HerdFormat::Toml => toml::from_str(content).map_err(parse_error)?,
+        };
+
+        Shop::from_records(herd.herd, herd.farm, config)
+    }
+}
+
+impl HerdFormat {
+    /// Serializes the herd of `shop` in this format, with the yaks at their current age. Yaks which
+    /// have died are left out unless `include_dead` is set. See `Shop::to_xml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the herd can't be represented in this format.
+    pub fn write(self, shop: &Shop, include_dead: bool) -> Result<String, YakShopError> {
+        let herd = Herd {
+            farm: shop.farm_overrides(),
+            herd: shop
+                .yaks()
+                .iter()
+                .filter(|yak| include_dead || yak.is_alive())
+                .map(Yak::to_record)
+                .collect(),
+        };
+
+        let write_error = |err: &dyn Display| {
+            YakShopError::InvalidParameter(format!("Can't write the herd as {self}: {err}"))
+        };
+
+        match self {
+            HerdFormat::Xml => Ok(shop.to_xml(include_dead)),
+            HerdFormat::Json => {
+                serde_json::to_string_pretty(&herd).map_err(|err| write_error(&err))
+            }
+            HerdFormat::Yaml => serde_yaml::to_string(&herd).map_err(|err| write_error(&err)),
+            HerdFormat::Toml => toml::to_string(&herd).map_err(|err| write_error(&err)),
+        }
+    }
+}
+
+fn parse_error<E: fmt::Debug>(err: E) -> YakShopError {
+    YakShopError::ConfigFileParseError(format!("{err:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            HerdFormat::from_path(&PathBuf::from("herd.xml")),
+            Some(HerdFormat::Xml)
+        );
+        assert_eq!(
+            HerdFormat::from_path(&PathBuf::from("herd.JSON")),
+            Some(HerdFormat::Json)
+        );
+        assert_eq!(
+            HerdFormat::from_path(&PathBuf::from("herd.yml")),
+            Some(HerdFormat::Yaml)
+        );
+        assert_eq!(
+            HerdFormat::from_path(&PathBuf::from("herd.toml")),
+            Some(HerdFormat::Toml)
+        );
+        assert_eq!(HerdFormat::from_path(&PathBuf::from("herd")), None);
+        assert_eq!(HerdFormat::from_path(&PathBuf::from("herd.txt")), None);
+    }
+
+    #[test]
+    fn test_from_str_unknown() {
+        assert!(matches!(
+            "csv".parse::<HerdFormat>(),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(HerdFormat::detect("  <herd></herd>"), HerdFormat::Xml);
+        assert_eq!(HerdFormat::detect(r#"{"herd": []}"#), HerdFormat::Json);
+        assert_eq!(
+            HerdFormat::detect("[[herd]]\nname = \"Betty-1\""),
+            HerdFormat::Toml
+        );
+        assert_eq!(HerdFormat::detect("herd = []"), HerdFormat::Toml);
+        assert_eq!(
+            HerdFormat::detect("herd:\n  - name: Betty-1"),
+            HerdFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_age() {
+        let result = HerdFormat::Json.parse(
+            r#"{"herd": [{"name": "Betty-1", "age": -1}]}"#,
+            &ShopConfig::default(),
+        );
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+
+    #[test]
+    fn test_parse_farm_overrides() {
+        let config = ShopConfig::default();
+        let herds = [
+            (
+                HerdFormat::Xml,
+                r#"<herd><farm days_in_yak_year="365" max_yak_age="3650"/><labyak name="Betty-1" age="4"/></herd>"#,
+            ),
+            (
+                HerdFormat::Json,
+                r#"{"farm": {"days_in_yak_year": 365, "max_yak_age": 3650}, "herd": [{"name": "Betty-1", "age": 4}]}"#,
+            ),
+            (
+                HerdFormat::Yaml,
+                "farm:\n  days_in_yak_year: 365\n  max_yak_age: 3650\nherd:\n  - name: Betty-1\n    age: 4",
+            ),
+            (
+                HerdFormat::Toml,
+                "farm = { days_in_yak_year = 365, max_yak_age = 3650 }\n[[herd]]\nname = \"Betty-1\"\nage = 4",
+            ),
+        ];
+
+        for (format, herd) in herds {
+            let shop = format.parse(herd, &config).unwrap();
+            assert_eq!(shop.config().farm.days_in_yak_year, 365, "{format}");
+            assert_eq!(shop.yaks()[0].days_remaining(), 3_650 - 4 * 365, "{format}");
+        }
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let herd = r#"{"farm": {"max_yak_age": 2000}, "herd": [{"name": "Betty-1", "age": 4, "sex": "f"}]}"#;
+        let mut shop = HerdFormat::Json
+            .parse(herd, &ShopConfig::default())
+            .unwrap();
+        shop.step_days(13).unwrap();
+
+        for format in [
+            HerdFormat::Xml,
+            HerdFormat::Json,
+            HerdFormat::Yaml,
+            HerdFormat::Toml,
+        ] {
+            let written = format.write(&shop, true).unwrap();
+            let loaded = format.parse(&written, &ShopConfig::default()).unwrap();
+
+            assert_eq!(loaded.config(), shop.config(), "{format}");
+            assert_eq!(loaded.yaks()[0].name(), "Betty-1", "{format}");
+            assert_eq!(loaded.yaks()[0].sex(), shop.yaks()[0].sex(), "{format}");
+            assert_eq!(
+                loaded.yaks()[0].age_in_days(),
+                shop.yaks()[0].age_in_days(),
+                "{format}"
+            );
+            assert_eq!(
+                loaded.yaks()[0].next_shave_day(),
+                shop.yaks()[0].next_shave_day(),
+                "{format}"
+            );
+        }
+
+        assert_eq!(
+            HerdFormat::Json.write(&shop, true).unwrap(),
+            r#"{
+  "farm": {
+    "max_yak_age": 2000
+  },
+  "herd": [
+    {
+      "name": "Betty-1",
+      "age": 4.13,
+      "sex": "f",
+      "age_last_shaved": 4.0
+    }
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_farm_overrides() {
+        let result = HerdFormat::Json.parse(
+            r#"{"farm": {"days_in_yak_year": 0}, "herd": []}"#,
+            &ShopConfig::default(),
+        );
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+
+        let result = HerdFormat::Json.parse(
+            r#"{"farm": {"days_in_a_year": 365}, "herd": []}"#,
+            &ShopConfig::default(),
+        );
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+}
@@ -0,0 +1,871 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Order, Products, Quantity, Sex, Shop, YakShopError};
+
+/// A change to the herd, see `Journal::edit_herd`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "edit", rename_all = "snake_case")]
+pub enum HerdEdit {
+    Added {
+        name: String,
+        /// In yak years
+        age: Quantity,
+        sex: Option<Sex>,
+    },
+    Renamed {
+        name: String,
+        new_name: String,
+    },
+    Aged {
+        name: String,
+        /// In yak years
+        age: Quantity,
+    },
+    Retired {
+        name: String,
+    },
+}
+
+impl HerdEdit {
+    /// Makes the change to the herd of `shop`.
+    ///
+    /// # Errors
+    ///
+    /// See `Shop::add_yak`, `Shop::rename_yak`, `Shop::set_yak_age` and `Shop::retire_yak`.
+    pub fn apply(&self, shop: &mut Shop) -> Result<(), YakShopError> {
+        match self {
+            HerdEdit::Added { name, age, sex } => shop.add_yak(name, *age, *sex).map(|_| ()),
+            HerdEdit::Renamed { name, new_name } => shop.rename_yak(name, new_name).map(|_| ()),
+            HerdEdit::Aged { name, age } => shop.set_yak_age(name, *age).map(|_| ()),
+            HerdEdit::Retired { name } => shop.retire_yak(name).map(|_| ()),
+        }
+    }
+}
+
+/// A change to the state of a shop, as recorded in its `Journal`. Every event happens on a shop
+/// day: orders, cancellations and herd edits at the start of the day, before the day passes.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The day passed, and the yaks produced `produced` on it.
+    DayStepped {
+        day: u32,
+        produced: Products,
+    },
+    /// A yak was shaved as the day passed.
+    Shaved {
+        day: u32,
+        yak: String,
+    },
+    /// A yak died of old age as the day passed.
+    Died {
+        day: u32,
+        yak: String,
+    },
+    /// An order was delivered from the stock, and recorded under `id`.
+    OrderDelivered {
+        id: u32,
+        order: Order,
+    },
+    /// An order delivered before the shop kept a journal, which wasn't taken from the stock. It
+    /// is taken from the stock as far as it can be when replayed, see `Journal::from_events`.
+    OrderImported {
+        id: u32,
+        order: Order,
+    },
+    /// The order recorded under `id` was cancelled, returning its products to the stock.
+    OrderCancelled {
+        day: u32,
+        id: u32,
+        returned: Products,
+    },
+    HerdEdited {
+        day: u32,
+        edit: HerdEdit,
+    },
+}
+
+impl Event {
+    /// The shop day the event happened on.
+    #[must_use]
+    pub fn day(&self) -> u32 {
+        match self {
+            Event::DayStepped { day, .. }
+            | Event::Shaved { day, .. }
+            | Event::Died { day, .. }
+            | Event::OrderCancelled { day, .. }
+            | Event::HerdEdited { day, .. } => *day,
+            Event::OrderDelivered { order, .. } | Event::OrderImported { order, .. } => order.day,
+        }
+    }
+
+    /// Whether the event was a decision, rather than following from the herd as the days passed.
+    /// Replaying the decisions on the same herd gives back all other events.
+    #[must_use]
+    pub fn is_decision(&self) -> bool {
+        matches!(
+            self,
+            Event::OrderDelivered { .. }
+                | Event::OrderImported { .. }
+                | Event::OrderCancelled { .. }
+                | Event::HerdEdited { .. }
+        )
+    }
+
+    /// Whether the event happened before the shop reached `day`, which leaves out the day steps
+    /// (and the shaves and deaths) of `day` itself.
+    fn before(&self, day: u32) -> bool {
+        match self {
+            Event::DayStepped { day: stepped, .. }
+            | Event::Shaved { day: stepped, .. }
+            | Event::Died { day: stepped, .. } => *stepped < day,
+            _ => self.day() <= day,
+        }
+    }
+}
+
+/// How many days apart the journal keeps a copy of the shop, see `Checkpoint`.
+const CHECKPOINT_INTERVAL: u32 = 30;
+
+/// The shop and its stock as they were when the shop reached `day`, before the decisions of that
+/// day, so looking up a past day only replays the events since the checkpoint before it.
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    day: u32,
+    /// The number of events before the checkpoint
+    events: usize,
+    shop: Shop,
+    stock: Products,
+}
+
+/// Where a journal was, to go back to if the changes made since can't be kept, see
+/// `Journal::rewind`.
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    origin: Shop,
+    origin_events: usize,
+    shop: Shop,
+    stock: Products,
+    events: usize,
+    checkpoints: usize,
+    next_order_id: u32,
+}
+
+/// The products delivered for an order, see `Journal::place_order`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// The id the order was recorded under, or `None` if nothing could be delivered
+    pub id: Option<u32>,
+    pub milk: Option<Quantity>,
+    pub wool: Option<u32>,
+}
+
+/// A shop which keeps every change to its state as an append-only list of events, starting from
+/// the herd the shop opened with. Unlike a plain `Shop`, orders take their products from the stock.
+///
+/// The events of the past are never changed, so the stock and the shop of any day up to today can
+/// be looked up from them, see `Journal::stock` and `Journal::replay`. To keep those lookups quick
+/// once the shop has been open for a while, the journal keeps a checkpoint of the shop every
+/// `CHECKPOINT_INTERVAL` days and replays from the closest one.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    /// The shop as it opened, or as it was when the journal last restarted, which the events
+    /// since are replayed on
+    origin: Shop,
+    /// The number of events before the origin, see `Journal::restart`
+    origin_events: usize,
+    /// The shop as it is today
+    shop: Shop,
+    /// The products produced and not sold
+    stock: Products,
+    events: Vec<Event>,
+    /// Ordered by day, starting with the shop as it opened
+    checkpoints: Vec<Checkpoint>,
+    next_order_id: u32,
+}
+
+impl Journal {
+    #[must_use]
+    pub fn new(origin: Shop) -> Self {
+        let checkpoint = Checkpoint {
+            day: origin.elapsed_days,
+            events: 0,
+            shop: origin.clone(),
+            stock: Products::default(),
+        };
+
+        Journal {
+            shop: origin.clone(),
+            origin,
+            origin_events: 0,
+            stock: Products::default(),
+            events: Vec::new(),
+            checkpoints: vec![checkpoint],
+            next_order_id: 1,
+        }
+    }
+
+    /// Starts a journal on `origin` and replays the decisions in `events` on it, such as the
+    /// events of another journal. All other events follow again from the herd, so they are taken
+    /// from the new herd if it differs. Imported orders become delivered orders, with what the
+    /// stock of their day can't deliver left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if a decision is for an earlier day than the one
+    /// before it, or the shop opened, or if an order can no longer be delivered from the stock of
+    /// its day, or any error of the herd edits.
+    pub fn from_events<'a>(
+        origin: Shop,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<Self, YakShopError> {
+        let mut journal = Journal::new(origin);
+        journal.replay_decisions(events)?;
+
+        Ok(journal)
+    }
+
+    /// A journal which continues from the shop and stock as they are today, without the events
+    /// before, to step forward to a day the shop hasn't reached without changing this journal.
+    /// Only the shop and stock are copied, however long the shop has been open.
+    #[must_use]
+    pub fn ahead(&self) -> Self {
+        let mut ahead = Journal::new(self.shop.clone());
+        ahead.stock = self.stock.clone();
+        ahead.checkpoints[0].stock = self.stock.clone();
+        ahead.next_order_id = self.next_order_id;
+        ahead
+    }
+
+    /// Where the journal is now. Only the shops and stock are copied, the events and checkpoints
+    /// are counted.
+    #[must_use]
+    pub fn bookmark(&self) -> Bookmark {
+        Bookmark {
+            origin: self.origin.clone(),
+            origin_events: self.origin_events,
+            shop: self.shop.clone(),
+            stock: self.stock.clone(),
+            events: self.events.len(),
+            checkpoints: self.checkpoints.len(),
+            next_order_id: self.next_order_id,
+        }
+    }
+
+    /// Goes back to where the journal was at `bookmark`, dropping the events and checkpoints
+    /// added since. Events are only ever added, so nothing before the bookmark is lost.
+    pub fn rewind(&mut self, bookmark: Bookmark) {
+        self.origin = bookmark.origin;
+        self.origin_events = bookmark.origin_events;
+        self.shop = bookmark.shop;
+        self.stock = bookmark.stock;
+        self.events.truncate(bookmark.events);
+        self.checkpoints.truncate(bookmark.checkpoints);
+        self.next_order_id = bookmark.next_order_id;
+    }
+
+    /// Continues the journal on the shop as it is today, which becomes its origin, such as when
+    /// the herd as it is today was written out. The events and checkpoints so far are kept, so
+    /// the days before can still be looked up, but `rebase` only replays the decisions since.
+    pub fn restart(&mut self) {
+        self.origin = self.shop.clone();
+        self.origin_events = self.events.len();
+        self.checkpoints.push(Checkpoint {
+            day: self.day(),
+            events: self.events.len(),
+            shop: self.shop.clone(),
+            stock: self.stock.clone(),
+        });
+    }
+
+    /// Replays the decisions since the origin on `herd`, which takes the place of the herd of the
+    /// origin, up to the same day. The events before the origin are kept as they are, and so is
+    /// the day, stock and expenses of the origin, which a herd file doesn't have.
+    ///
+    /// # Errors
+    ///
+    /// See `Journal::from_events`.
+    pub fn rebase(&self, herd: Shop) -> Result<Self, YakShopError> {
+        // The origin is the last checkpoint with the events before it, see `restart`
+        let kept = self
+            .checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.events <= self.origin_events)
+            .unwrap_or_default();
+        let start = &self.checkpoints[kept];
+
+        let mut origin = herd;
+        origin.elapsed_days = self.origin.elapsed_days;
+        origin.produced_products = self.origin.produced_products.clone();
+        origin.expenses = self.origin.expenses;
+
+        let mut checkpoints = self.checkpoints[..kept].to_vec();
+        checkpoints.push(Checkpoint {
+            shop: origin.clone(),
+            ..start.clone()
+        });
+
+        let events = self.events[..self.origin_events].to_vec();
+        let next_order_id = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::OrderDelivered { id, .. } => Some(id + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+
+        let mut rebased = Journal {
+            shop: origin.clone(),
+            origin,
+            origin_events: self.origin_events,
+            stock: start.stock.clone(),
+            events,
+            checkpoints,
+            next_order_id,
+        };
+        rebased.replay_decisions(&self.events[self.origin_events..])?;
+        rebased.advance(self.day())?;
+
+        Ok(rebased)
+    }
+
+    /// Replays the decisions in `events` on the journal, see `from_events`.
+    fn replay_decisions<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<(), YakShopError> {
+        for event in events.into_iter().filter(|event| event.is_decision()) {
+            if event.day() < self.day() {
+                return Err(YakShopError::InvalidParameter(format!(
+                    "An event of day {} comes after day {}",
+                    event.day(),
+                    self.day()
+                )));
+            }
+            self.advance(event.day())?;
+
+            match event {
+                Event::OrderDelivered { id, order } => {
+                    self.stock = self.stock.checked_sub(&order.delivered).ok_or_else(|| {
+                        YakShopError::InvalidParameter(format!(
+                            "Order {id} can't be delivered from the stock of day {}",
+                            order.day
+                        ))
+                    })?;
+                    self.next_order_id = self.next_order_id.max(id + 1);
+                    self.events.push(event.clone());
+                }
+                Event::OrderImported { id, order } => {
+                    let delivered = Products::new(
+                        order.delivered.milk().min(self.stock.milk()),
+                        order.delivered.wool().min(self.stock.wool()),
+                    );
+                    // SAFETY: Neither product is more than the stock
+                    self.stock = self.stock.checked_sub(&delivered).unwrap_or_default();
+                    self.next_order_id = self.next_order_id.max(id + 1);
+                    self.events.push(Event::OrderDelivered {
+                        id: *id,
+                        order: Order {
+                            delivered,
+                            ..order.clone()
+                        },
+                    });
+                }
+                Event::OrderCancelled { id, .. } => {
+                    self.cancel_order(*id)?;
+                }
+                Event::HerdEdited { edit, .. } => self.edit_herd(edit.clone())?,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The shop as it opened, or as it was when the journal last restarted.
+    #[must_use]
+    pub fn origin(&self) -> &Shop {
+        &self.origin
+    }
+
+    /// The shop as it opened, before any of the events, even if the journal restarted since.
+    #[must_use]
+    pub fn opened(&self) -> &Shop {
+        // The first checkpoint is the shop as it opened
+        &self.checkpoints[0].shop
+    }
+
+    /// The shop as it is today.
+    #[must_use]
+    pub fn shop(&self) -> &Shop {
+        &self.shop
+    }
+
+    /// The number of days since the shop opened.
+    #[must_use]
+    pub fn day(&self) -> u32 {
+        self.shop.elapsed_days
+    }
+
+    #[must_use]
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// The events up to when the shop reached `day`, including the decisions of that day but not
+    /// the day passing.
+    #[must_use]
+    pub fn events_until(&self, day: u32) -> &[Event] {
+        &self.events[..self.end_of(day, 0)]
+    }
+
+    /// The number of events up to when the shop reached `day`, knowing the first `start` events
+    /// come before it. The events are in the order of their days, so they are searched rather
+    /// than scanned.
+    fn end_of(&self, day: u32, start: usize) -> usize {
+        start + self.events[start..].partition_point(|event| event.before(day))
+    }
+
+    /// Steps the shop forward day by day until `day`, recording the day steps, shaves and deaths.
+    /// Days which have already passed are left as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the stock no longer fits, see `Shop::step_days`.
+    pub fn advance(&mut self, day: u32) -> Result<(), YakShopError> {
+        while self.day() < day {
+            let today = self.day();
+            let before: Vec<(usize, bool)> = self
+                .shop
+                .yaks()
+                .iter()
+                .map(|yak| (yak.shave_days().len(), yak.is_alive()))
+                .collect();
+
+            let produced = self.shop.step_days(1)?;
+            self.stock = self.stock.checked_add(&produced)?;
+            self.events.push(Event::DayStepped {
+                day: today,
+                produced,
+            });
+
+            for (yak, (shaves, alive)) in self.shop.yaks().iter().zip(before) {
+                let yak_name = yak.name().to_string();
+                if yak.shave_days().len() > shaves {
+                    self.events.push(Event::Shaved {
+                        day: today,
+                        yak: yak_name.clone(),
+                    });
+                }
+                if alive && !yak.is_alive() {
+                    self.events.push(Event::Died {
+                        day: today,
+                        yak: yak_name,
+                    });
+                }
+            }
+
+            if self.day() % CHECKPOINT_INTERVAL == 0 {
+                self.checkpoints.push(Checkpoint {
+                    day: self.day(),
+                    events: self.events.len(),
+                    shop: self.shop.clone(),
+                    stock: self.stock.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delivers what it can of an order from today's stock. Each product is either delivered in
+    /// full or not at all, as with `Shop::consume_products`.
+    #[must_use]
+    pub fn place_order(
+        &mut self,
+        customer: &str,
+        milk: Option<Quantity>,
+        wool: Option<u32>,
+    ) -> Delivery {
+        let (milk, wool) = self.stock.fill(milk, wool);
+        if (milk, wool) == (None, None) {
+            return Delivery {
+                id: None,
+                milk,
+                wool,
+            };
+        }
+
+        let order = self.shop.order(customer, (milk, wool));
+        // SAFETY: `fill` only delivers what is in stock
+        self.stock = self.stock.checked_sub(&order.delivered).unwrap_or_default();
+
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.events.push(Event::OrderDelivered { id, order });
+
+        Delivery {
+            id: Some(id),
+            milk,
+            wool,
+        }
+    }
+
+    /// Cancels an order today, returning its products to the stock.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::OrderNotFound` if there is no such order, or it was already
+    /// cancelled.
+    pub fn cancel_order(&mut self, id: u32) -> Result<Order, YakShopError> {
+        let order = self
+            .orders(self.day())
+            .into_iter()
+            .find_map(|(order_id, order)| (order_id == id).then_some(order))
+            .ok_or(YakShopError::OrderNotFound(id))?;
+
+        self.stock = self.stock.checked_add(&order.delivered)?;
+        self.events.push(Event::OrderCancelled {
+            day: self.day(),
+            id,
+            returned: order.delivered.clone(),
+        });
+
+        Ok(order)
+    }
+
+    /// Changes the herd today.
+    ///
+    /// # Errors
+    ///
+    /// See `HerdEdit::apply`.
+    pub fn edit_herd(&mut self, edit: HerdEdit) -> Result<(), YakShopError> {
+        edit.apply(&mut self.shop)?;
+        self.events.push(Event::HerdEdited {
+            day: self.day(),
+            edit,
+        });
+
+        Ok(())
+    }
+
+    /// The orders delivered and not cancelled by `day`, by id.
+    #[must_use]
+    pub fn orders(&self, day: u32) -> Vec<(u32, Order)> {
+        let mut orders = Vec::new();
+        for event in self.events_until(day) {
+            match event {
+                Event::OrderDelivered { id, order } => orders.push((*id, order.clone())),
+                Event::OrderCancelled { id, .. } => orders.retain(|(order_id, _)| order_id != id),
+                _ => {}
+            }
+        }
+        orders
+    }
+
+    /// The products in stock when the shop reached `day`, after that day's orders, or `None` if
+    /// the shop has not reached that day yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the stock does not fit, which `advance` rules out.
+    pub fn stock(&self, day: u32) -> Result<Option<Products>, YakShopError> {
+        if day > self.day() {
+            return Ok(None);
+        }
+
+        let checkpoint = self.checkpoint(day);
+        let mut stock = checkpoint.stock.clone();
+        for event in &self.events[checkpoint.events..self.end_of(day, checkpoint.events)] {
+            match event {
+                Event::DayStepped { produced, .. } => stock = stock.checked_add(produced)?,
+                Event::OrderCancelled { returned, .. } => stock = stock.checked_add(returned)?,
+                Event::OrderDelivered { order, .. } => {
+                    // SAFETY: Orders are only delivered from the stock
+                    stock = stock.checked_sub(&order.delivered).unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Some(stock))
+    }
+
+    /// The last checkpoint on or before `day`.
+    fn checkpoint(&self, day: u32) -> &Checkpoint {
+        let after = self
+            .checkpoints
+            .partition_point(|checkpoint| checkpoint.day <= day);
+        // The first checkpoint is the shop as it opened, which comes before any day
+        &self.checkpoints[after.saturating_sub(1)]
+    }
+
+    /// The shop as it was when it reached `day`, rebuilt from the closest checkpoint before it by
+    /// replaying the events since.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the shop has not reached `day` yet.
+    pub fn replay(&self, day: u32) -> Result<Shop, YakShopError> {
+        if day > self.day() {
+            return Err(YakShopError::InvalidParameter(format!(
+                "The shop has not reached day {day} yet"
+            )));
+        }
+
+        let checkpoint = self.checkpoint(day);
+        let mut shop = checkpoint.shop.clone();
+        for event in &self.events[checkpoint.events..self.end_of(day, checkpoint.events)] {
+            match event {
+                Event::DayStepped { .. } => {
+                    shop.step_days(1)?;
+                }
+                Event::HerdEdited { edit, .. } => edit.apply(&mut shop)?,
+                _ => {}
+            }
+        }
+
+        Ok(shop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShopConfig;
+    use std::path::PathBuf;
+
+    fn amount(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
+    fn journal() -> Journal {
+        let herd = PathBuf::from("./tests/fixtures/valid_multi.xml");
+        Journal::new(Shop::from_path(&herd, None, &ShopConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+
+        assert_eq!(journal.shop().produced_products.milk(), amount("1104.48"));
+        assert_eq!(
+            journal.stock(13).unwrap(),
+            Some(Products::new(amount("1104.48"), 3))
+        );
+
+        let shaves: Vec<(u32, &str)> = journal
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Shaved { day, yak } => Some((*day, yak.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(shaves, [(0, "Betty-1"), (0, "Betty-2"), (0, "Betty-3")]);
+
+        // Stepping to a day which has passed changes nothing
+        journal.advance(5).unwrap();
+        assert_eq!(journal.day(), 13);
+    }
+
+    #[test]
+    fn test_orders() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+
+        let delivery = journal.place_order("Medvedev", Some(amount("1100")), Some(3));
+        assert_eq!(delivery.id, Some(1));
+        assert_eq!(
+            journal.stock(13).unwrap(),
+            Some(Products::new(amount("4.48"), 0))
+        );
+
+        // The stock is gone, so only what was made since can be delivered
+        journal.advance(14).unwrap();
+        let delivery = journal.place_order("Putin", Some(amount("50")), Some(2));
+        assert_eq!((delivery.milk, delivery.wool), (Some(amount("50")), None));
+
+        let cancelled = journal.cancel_order(1).unwrap();
+        assert_eq!(cancelled.customer, "Medvedev");
+        assert!(journal.cancel_order(1).is_err());
+        assert_eq!(journal.orders(journal.day()).len(), 1);
+
+        // The past stays as it was
+        assert_eq!(journal.orders(13)[0].0, 1);
+        assert_eq!(
+            journal.stock(13).unwrap(),
+            Some(Products::new(amount("4.48"), 0))
+        );
+        assert_eq!(
+            journal.stock(14).unwrap(),
+            Some(Products::new(amount("1188.81") - amount("50"), 4))
+        );
+        assert_eq!(journal.stock(15).unwrap(), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut journal = journal();
+        journal.advance(10).unwrap();
+        journal
+            .edit_herd(HerdEdit::Retired {
+                name: "Betty-3".to_string(),
+            })
+            .unwrap();
+        journal.advance(13).unwrap();
+
+        let shop = journal.replay(13).unwrap();
+        assert_eq!(shop.produced_products, journal.shop().produced_products);
+        assert_eq!(shop.yaks().len(), 2);
+
+        let shop = journal.replay(10).unwrap();
+        assert_eq!(shop.yaks().len(), 2);
+        let shop = journal.replay(9).unwrap();
+        assert_eq!(shop.yaks().len(), 3);
+        assert_eq!(shop.elapsed_days, 9);
+
+        assert!(journal.replay(14).is_err());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let mut journal = journal();
+        journal.advance(CHECKPOINT_INTERVAL).unwrap();
+        let _ = journal.place_order("Medvedev", Some(amount("1100")), Some(3));
+        journal.advance(45).unwrap();
+        journal
+            .edit_herd(HerdEdit::Retired {
+                name: "Betty-3".to_string(),
+            })
+            .unwrap();
+        journal.advance(100).unwrap();
+        assert_eq!(journal.checkpoints.len(), 4);
+
+        // Looking up a past day gives the same as replaying everything up to it from the start
+        for day in 0..=100 {
+            let scanned = journal.events.iter().take_while(|event| event.before(day));
+            assert_eq!(journal.events_until(day).len(), scanned.count());
+
+            let mut replayed =
+                Journal::from_events(journal.origin().clone(), journal.events_until(day)).unwrap();
+            replayed.advance(day).unwrap();
+
+            assert_eq!(journal.stock(day).unwrap().as_ref(), Some(&replayed.stock));
+            assert_eq!(
+                journal.replay(day).unwrap().to_xml(true),
+                replayed.shop().to_xml(true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+        let bookmark = journal.bookmark();
+        let (events, stock) = (journal.events().to_vec(), journal.stock(13).unwrap());
+
+        journal.advance(40).unwrap();
+        let _ = journal.place_order("Medvedev", Some(amount("1000")), Some(1));
+        journal.restart();
+
+        journal.rewind(bookmark);
+        assert_eq!(journal.day(), 13);
+        assert_eq!(journal.events(), events);
+        assert_eq!(journal.stock(13).unwrap(), stock);
+        assert_eq!(journal.stock(40).unwrap(), None);
+
+        // The order ids are given out again
+        journal.advance(40).unwrap();
+        let delivery = journal.place_order("Medvedev", Some(amount("1000")), Some(1));
+        assert_eq!(delivery.id, Some(1));
+    }
+
+    #[test]
+    fn test_ahead() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+        let _ = journal.place_order("Medvedev", Some(amount("1000")), Some(1));
+
+        let mut ahead = journal.ahead();
+        ahead.advance(20).unwrap();
+        assert!(ahead.events().iter().all(|event| event.day() >= 13));
+
+        journal.advance(20).unwrap();
+        assert_eq!(ahead.stock(20).unwrap(), journal.stock(20).unwrap());
+        assert_eq!(
+            ahead.replay(20).unwrap().to_xml(true),
+            journal.replay(20).unwrap().to_xml(true)
+        );
+        assert_eq!(
+            ahead.events(),
+            journal.events_until(20)[journal.end_of(13, 0)..].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+        let _ = journal.place_order("Medvedev", Some(amount("1000")), Some(1));
+        journal.restart();
+        let _ = journal.place_order("Putin", Some(amount("100")), None);
+        journal.advance(20).unwrap();
+
+        assert_eq!(journal.origin().elapsed_days, 13);
+        assert_eq!(journal.opened().elapsed_days, 0);
+        assert_eq!(
+            journal.replay(13).unwrap().to_xml(true),
+            journal.origin().to_xml(true)
+        );
+
+        // Only the order since the restart is replayed, on the herd as it was then
+        let rebased = journal.rebase(journal.origin().clone()).unwrap();
+        assert_eq!(rebased.events(), journal.events());
+        assert_eq!(rebased.stock(13).unwrap(), journal.stock(13).unwrap());
+        assert_eq!(rebased.stock(20).unwrap(), journal.stock(20).unwrap());
+        assert_eq!(rebased.orders(20).len(), 2);
+    }
+
+    #[test]
+    fn test_from_events() {
+        let mut journal = journal();
+        journal.advance(13).unwrap();
+        let _ = journal.place_order("Medvedev", Some(amount("1100")), Some(3));
+        journal
+            .edit_herd(HerdEdit::Added {
+                name: "Dolma".to_string(),
+                age: amount("2.5"),
+                sex: None,
+            })
+            .unwrap();
+
+        let events = serde_json::to_string(journal.events()).unwrap();
+        let events: Vec<Event> = serde_json::from_str(&events).unwrap();
+
+        let replayed = Journal::from_events(journal.origin().clone(), &events).unwrap();
+        assert_eq!(replayed.events(), journal.events());
+        assert_eq!(replayed.stock(13).unwrap(), journal.stock(13).unwrap());
+
+        // A herd without Betty-1 does not produce enough milk for the order
+        let mut origin = journal.origin().clone();
+        origin.retire_yak("Betty-1").unwrap();
+        let result = Journal::from_events(origin, &events);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+
+        // The events are in the order of their days, so an earlier day can't follow a later one
+        let imported = |day| Event::OrderImported {
+            id: day,
+            order: Order {
+                customer: "Medvedev".to_string(),
+                day,
+                delivered: Products::new(amount("1"), 0),
+            },
+        };
+        let result = Journal::from_events(journal.origin().clone(), &[imported(40), imported(13)]);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+}
@@ -15,20 +15,46 @@
 #[macro_use]
 extern crate approx;
 
+mod calendar;
+mod config;
+mod cooperative;
+mod economics;
+mod format;
+mod journal;
+mod quantity;
+mod seasons;
 mod shop;
+mod simulation;
 mod yak;
 
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 use std::path::PathBuf;
 
+pub use calendar::{Calendar, ShopDay};
+pub use chrono::NaiveDate;
+pub use config::{FarmConfig, ShopConfig, DEFAULT_CONFIG_FILE};
+pub use cooperative::Cooperative;
+pub use economics::{CostConfig, Expenses, Order, Prices, ProfitAndLoss};
+pub use format::HerdFormat;
+pub use journal::{Bookmark, Delivery, Event, HerdEdit, Journal};
+pub use quantity::Quantity;
+pub use seasons::{Season, Seasons};
 pub use shop::Shop;
-pub use yak::{Products, Yak};
+pub use simulation::{Distribution, MonteCarlo, SimulationReport, StochasticModel};
+pub use yak::{Products, Sex, Yak};
 
 #[derive(Debug)]
 pub enum YakShopError {
     ConfigFileNotFound(PathBuf),
+    ConfigFileReadError(std::io::Error),
     ConfigFileParseError(String),
+    ConfigFileWriteError(std::io::Error),
+    StorageError(String),
+    InvalidParameter(String),
+    Overflow(String),
+    YakNotFound(String),
+    OrderNotFound(u32),
 }
 
 impl Display for YakShopError {
@@ -37,9 +63,20 @@ impl Display for YakShopError {
             YakShopError::ConfigFileNotFound(path) => {
                 write!(f, "Config file {} not found", path.to_string_lossy())
             }
+            YakShopError::ConfigFileReadError(err) => {
+                write!(f, "Error reading config file: {err}")
+            }
             YakShopError::ConfigFileParseError(msg) => {
                 write!(f, "Error parsing config file: {msg}")
             }
+            YakShopError::ConfigFileWriteError(err) => {
+                write!(f, "Error writing config file: {err}")
+            }
+            YakShopError::StorageError(what) => write!(f, "Failed to store {what}"),
+            YakShopError::InvalidParameter(msg) => write!(f, "Invalid parameter: {msg}"),
+            YakShopError::Overflow(total) => write!(f, "The {total} total overflowed"),
+            YakShopError::YakNotFound(name) => write!(f, "There is no yak named {name}"),
+            YakShopError::OrderNotFound(id) => write!(f, "There is no order {id}"),
         }
     }
 }
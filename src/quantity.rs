@@ -0,0 +1,341 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, AddAssign, Sub};
+use std::str::FromStr;
+
+use crate::YakShopError;
+
+/// The number of decimal places a `Quantity` is exact to.
+const DECIMAL_PLACES: usize = 3;
+
+/// A non-negative decimal amount (e.g. liters of milk), stored as an exact number of thousandths.
+///
+/// Values with more than three decimal places are rounded to the nearest thousandth, with halves
+/// rounded up (e.g. `1.0005` becomes `1.001`). All arithmetic and comparisons are done on the
+/// integer representation, floats are only used to present the value.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quantity(u64);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0);
+    /// The number of thousandths in one whole unit.
+    pub const SCALE: u64 = 1_000;
+
+    #[must_use]
+    pub const fn from_thousandths(thousandths: u64) -> Self {
+        Quantity(thousandths)
+    }
+
+    #[must_use]
+    pub const fn from_whole(whole: u64) -> Self {
+        Quantity(whole * Self::SCALE)
+    }
+
+    #[must_use]
+    pub const fn thousandths(self) -> u64 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    #[must_use]
+    pub fn checked_add(self, rhs: Quantity) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Quantity)
+    }
+
+    #[must_use]
+    pub fn checked_sub(self, rhs: Quantity) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Quantity)
+    }
+
+    /// Multiplies by `factor`, rounding to the nearest thousandth with halves rounded up.
+    #[must_use]
+    pub fn checked_scale(self, factor: Quantity) -> Option<Self> {
+        let scaled = (u128::from(self.0) * u128::from(factor.0) + u128::from(Self::SCALE / 2))
+            / u128::from(Self::SCALE);
+        u64::try_from(scaled).ok().map(Quantity)
+    }
+
+    #[must_use]
+    pub fn saturating_sub(self, rhs: Quantity) -> Self {
+        Quantity(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Converts a float to the nearest thousandth.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the value is NaN, infinite, negative or too
+    /// large.
+    pub fn try_from_f64(value: f64) -> Result<Self, YakShopError> {
+        let thousandths = (value * 1_000.0).round();
+
+        match thousandths {
+            v if v.is_nan() => Err(invalid_quantity("Quantity is NaN")),
+            v if v.is_infinite() => Err(invalid_quantity("Quantity is infinite")),
+            v if v < 0.0 => Err(invalid_quantity("Quantity cannot be negative")),
+            // u64::MAX is not exactly representable as a float, 2^64 is the next value up
+            v if v >= 18_446_744_073_709_551_616.0 => {
+                Err(invalid_quantity("Quantity is too large"))
+            }
+            _ => {
+                // SAFETY: The following allows have been checked above
+                #[allow(clippy::cast_possible_truncation)]
+                #[allow(clippy::cast_sign_loss)]
+                Ok(Quantity(thousandths as u64))
+            }
+        }
+    }
+
+    /// The value as a float, for presentation only.
+    #[must_use]
+    pub fn to_f64(self) -> f64 {
+        // SAFETY: Precision is only lost above 2^53 thousandths, way past any realistic quantity
+        #[allow(clippy::cast_precision_loss)]
+        let value = self.0 as f64 / 1_000.0;
+        value
+    }
+}
+
+fn invalid_quantity(msg: &str) -> YakShopError {
+    YakShopError::InvalidParameter(msg.to_string())
+}
+
+// The operators panic rather than wrap around in release builds. Use `checked_add` and
+// `checked_sub` where the result is not known to fit.
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, rhs: Quantity) -> Quantity {
+        self.checked_add(rhs).expect("Quantity overflowed")
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, rhs: Quantity) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, rhs: Quantity) -> Quantity {
+        self.checked_sub(rhs).expect("Quantity underflowed")
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Pad so width and alignment flags work as they do for numbers
+        f.pad_integral(
+            true,
+            "",
+            &format!(
+                "{}.{:0width$}",
+                self.0 / Self::SCALE,
+                self.0 % Self::SCALE,
+                width = DECIMAL_PLACES
+            ),
+        )
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = YakShopError;
+
+    /// Parses a plain decimal number such as `1104.48`, without going through a float.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(invalid_quantity(&format!(
+                "{value} is not a valid quantity"
+            )));
+        }
+
+        let too_large = || invalid_quantity(&format!("{value} is too large"));
+
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| too_large())?
+        };
+
+        // Pad or cut the fraction to the supported decimal places, then round on the next digit
+        let mut thousandths = 0;
+        for digit in fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(DECIMAL_PLACES)
+        {
+            thousandths = thousandths * 10 + u64::from(digit - b'0');
+        }
+        if fraction.as_bytes().get(DECIMAL_PLACES) >= Some(&b'5') {
+            thousandths += 1;
+        }
+
+        whole
+            .checked_mul(Self::SCALE)
+            .and_then(|whole| whole.checked_add(thousandths))
+            .map(Quantity)
+            .ok_or_else(too_large)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+struct QuantityVisitor;
+
+impl Visitor<'_> for QuantityVisitor {
+    type Value = Quantity;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "a non-negative decimal number")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Quantity, E> {
+        value
+            .checked_mul(Quantity::SCALE)
+            .map(Quantity)
+            .ok_or_else(|| E::custom("Quantity is too large"))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Quantity, E> {
+        let value = u64::try_from(value).map_err(|_| E::custom("Quantity cannot be negative"))?;
+        self.visit_u64(value)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Quantity, E> {
+        Quantity::try_from_f64(value).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Quantity, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(QuantityVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(parse("1104.48"), Quantity::from_thousandths(1_104_480));
+        assert_eq!(parse("13"), Quantity::from_whole(13));
+        assert_eq!(parse("0.5"), Quantity::from_thousandths(500));
+        assert_eq!(parse(".25"), Quantity::from_thousandths(250));
+        assert_eq!(parse("7."), Quantity::from_whole(7));
+    }
+
+    #[test]
+    fn test_from_str_rounding() {
+        assert_eq!(parse("1.0004"), Quantity::from_thousandths(1_000));
+        assert_eq!(parse("1.0005"), Quantity::from_thousandths(1_001));
+        assert_eq!(parse("1.0009999"), Quantity::from_thousandths(1_001));
+    }
+
+    #[test]
+    fn test_from_str_invalid() {
+        for value in ["", ".", "-1", "1e3", "abc", "1.2.3", "NaN"] {
+            assert!(
+                matches!(
+                    value.parse::<Quantity>(),
+                    Err(YakShopError::InvalidParameter(_))
+                ),
+                "{value} should not parse"
+            );
+        }
+        assert!("99999999999999999999".parse::<Quantity>().is_err());
+    }
+
+    #[test]
+    fn test_try_from_f64() {
+        // 1104.48 is 1104.4799999999999... as a float
+        assert_eq!(
+            Quantity::try_from_f64(1104.48).unwrap(),
+            Quantity::from_thousandths(1_104_480)
+        );
+        assert!(Quantity::try_from_f64(-0.01).is_err());
+        assert!(Quantity::try_from_f64(f64::NAN).is_err());
+        assert!(Quantity::try_from_f64(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(parse("1104.48").to_string(), "1104.480");
+        assert_eq!(Quantity::ZERO.to_string(), "0.000");
+        assert_eq!(parse("0.007").to_string(), "0.007");
+        assert_eq!(format!("{:>8}", parse("1.5")), "   1.500");
+    }
+
+    #[test]
+    fn test_json() {
+        let quantity: Quantity = serde_json::from_str("1104.48").unwrap();
+        assert_eq!(quantity, parse("1104.48"));
+        assert_eq!(serde_json::to_string(&quantity).unwrap(), "1104.48");
+
+        let quantity: Quantity = serde_json::from_str("3").unwrap();
+        assert_eq!(quantity, Quantity::from_whole(3));
+
+        assert!(serde_json::from_str::<Quantity>("-3").is_err());
+    }
+
+    #[test]
+    fn test_checked() {
+        let max = Quantity::from_thousandths(u64::MAX);
+        assert_eq!(max.checked_add(Quantity::from_thousandths(1)), None);
+        assert_eq!(Quantity::ZERO.checked_sub(parse("0.001")), None);
+        assert_eq!(parse("1.5").checked_sub(parse("0.5")), Some(parse("1")));
+    }
+
+    #[test]
+    fn test_checked_scale() {
+        assert_eq!(parse("40").checked_scale(parse("0.8")), Some(parse("32")));
+        assert_eq!(
+            parse("1.5").checked_scale(Quantity::ZERO),
+            Some(Quantity::ZERO)
+        );
+        // 0.005 * 0.1 = 0.0005, rounded up
+        assert_eq!(
+            parse("0.005").checked_scale(parse("0.1")),
+            Some(parse("0.001"))
+        );
+        assert_eq!(
+            Quantity::from_thousandths(u64::MAX).checked_scale(parse("2")),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Quantity overflowed")]
+    fn test_add_overflow_panics() {
+        let _ = Quantity::from_thousandths(u64::MAX) + Quantity::from_thousandths(1);
+    }
+
+    #[test]
+    fn test_comparison() {
+        assert!(parse("1104.48") >= parse("1104.479999"));
+        assert!(parse("1104.48") < parse("1104.481"));
+    }
+}
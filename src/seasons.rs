@@ -0,0 +1,265 @@
+use chrono::Datelike;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{Calendar, FarmConfig, Quantity, YakShopError};
+
+/// The largest seasonal multiplier allowed, see `FarmConfig::validate` for why it is bounded.
+const MAX_MULTIPLIER: Quantity = Quantity::from_whole(10);
+
+/// A season of the year, during which milk yield and wool growth are multiplied, e.g.:
+///
+/// ```toml
+/// [[seasons]]
+/// name = "winter"
+/// months = [12, 1, 2]
+/// milk = 0.7
+/// wool = 0.5
+/// ```
+///
+/// Seasons are either placed on the yak year with `start_day`, lasting until the next season
+/// starts, or on the calendar with `months`. Days outside of any season produce as normal.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Season {
+    pub name: String,
+    /// The day of the yak year (counted from the day the shop opened) the season starts on.
+    #[serde(default)]
+    pub start_day: Option<u32>,
+    /// The calendar months (1 to 12) of the season, which requires a calendar start date.
+    #[serde(default)]
+    pub months: Vec<u32>,
+    /// The multiplier of the daily milk yield.
+    #[serde(default = "Season::no_change")]
+    pub milk: Quantity,
+    /// The multiplier of the wool growth. At `0.5`, the time until a yak can be shaved again
+    /// doubles.
+    #[serde(default = "Season::no_change")]
+    pub wool: Quantity,
+}
+
+impl Season {
+    fn no_change() -> Quantity {
+        Quantity::from_whole(1)
+    }
+}
+
+/// The seasons of a shop, resolved against its calendar and farm so the season of any shop day can
+/// be looked up. Cheap to clone, as every yak keeps a copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seasons {
+    /// Sorted by `start_day`
+    all: Arc<[Season]>,
+    calendar: Calendar,
+    days_in_yak_year: u32,
+}
+
+// Written out, as `Arc<[T]>` only implements `Default` from Rust 1.80
+impl Default for Seasons {
+    fn default() -> Self {
+        Seasons {
+            all: Arc::from(Vec::new()),
+            calendar: Calendar::default(),
+            days_in_yak_year: 0,
+        }
+    }
+}
+
+impl Seasons {
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the seasons mix `start_day` and `months`, place
+    /// a season outside of the year, use months without a calendar start date, or have a
+    /// multiplier out of range.
+    pub fn new(
+        seasons: &[Season],
+        calendar: Calendar,
+        farm: &FarmConfig,
+    ) -> Result<Self, YakShopError> {
+        let by_day = seasons.iter().filter(|s| s.start_day.is_some()).count();
+        if by_day != 0 && by_day != seasons.len() {
+            return Err(invalid_season(
+                "Seasons must all have either a start_day or months",
+            ));
+        }
+
+        for season in seasons {
+            let name = &season.name;
+
+            match season.start_day {
+                Some(day) if day >= farm.days_in_yak_year => {
+                    return Err(invalid_season(&format!(
+                        "The {name} season starts after the end of the yak year"
+                    )));
+                }
+                None if season.months.is_empty() => {
+                    return Err(invalid_season(&format!(
+                        "The {name} season needs a start_day or months"
+                    )));
+                }
+                None if calendar.start_date.is_none() => {
+                    return Err(invalid_season(&format!(
+                        "The {name} season uses months, which requires a calendar start date"
+                    )));
+                }
+                _ => {}
+            }
+
+            if let Some(month) = season.months.iter().find(|m| !(1..=12).contains(*m)) {
+                return Err(invalid_season(&format!(
+                    "The {name} season has an invalid month {month}"
+                )));
+            }
+            if season.milk > MAX_MULTIPLIER || season.wool > MAX_MULTIPLIER {
+                return Err(invalid_season(&format!(
+                    "The {name} season multipliers must be at most {MAX_MULTIPLIER}"
+                )));
+            }
+            if season.wool == Quantity::ZERO {
+                return Err(invalid_season(&format!(
+                    "The {name} season must have some wool growth"
+                )));
+            }
+        }
+
+        let mut seasons = seasons.to_vec();
+        seasons.sort_by_key(|season| season.start_day);
+
+        Ok(Seasons {
+            all: seasons.into(),
+            calendar,
+            days_in_yak_year: farm.days_in_yak_year,
+        })
+    }
+
+    /// The season on the given day since the shop opened, if any.
+    #[must_use]
+    pub fn season(&self, day: u32) -> Option<&Season> {
+        let first = self.all.first()?;
+
+        if first.start_day.is_some() {
+            let day_of_year = day % self.days_in_yak_year;
+
+            // The last season to start on or before today, or else the last season of last year
+            return self
+                .all
+                .iter()
+                .rev()
+                .find(|season| season.start_day <= Some(day_of_year))
+                .or(self.all.last());
+        }
+
+        let month = self.calendar.date(day)?.month();
+        self.all
+            .iter()
+            .find(|season| season.months.contains(&month))
+    }
+
+    /// The milk yield multiplier on the given day.
+    #[must_use]
+    pub fn milk(&self, day: u32) -> Quantity {
+        self.season(day)
+            .map_or_else(Season::no_change, |season| season.milk)
+    }
+
+    /// The wool growth multiplier on the given day.
+    #[must_use]
+    pub fn wool(&self, day: u32) -> Quantity {
+        self.season(day)
+            .map_or_else(Season::no_change, |season| season.wool)
+    }
+}
+
+fn invalid_season(msg: &str) -> YakShopError {
+    YakShopError::InvalidParameter(msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn season(name: &str, start_day: Option<u32>, months: &[u32], milk: &str) -> Season {
+        Season {
+            name: name.to_string(),
+            start_day,
+            months: months.to_vec(),
+            milk: milk.parse().unwrap(),
+            wool: Season::no_change(),
+        }
+    }
+
+    fn calendar() -> Calendar {
+        Calendar {
+            start_date: Some("2026-10-01".parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_yak_year_seasons() {
+        let seasons = Seasons::new(
+            &[
+                season("summer", Some(50), &[], "1.2"),
+                season("winter", Some(10), &[], "0.5"),
+            ],
+            Calendar::default(),
+            &FarmConfig::default(),
+        )
+        .unwrap();
+
+        // Before the first season starts, it is still the last season of the previous year
+        assert_eq!(seasons.milk(0), "1.2".parse().unwrap());
+        assert_eq!(seasons.milk(10), "0.5".parse().unwrap());
+        assert_eq!(seasons.milk(49), "0.5".parse().unwrap());
+        assert_eq!(seasons.milk(50), "1.2".parse().unwrap());
+        assert_eq!(seasons.milk(110), "0.5".parse().unwrap());
+        assert_eq!(seasons.wool(110), Quantity::from_whole(1));
+    }
+
+    #[test]
+    fn test_month_seasons() {
+        let seasons = Seasons::new(
+            &[season("winter", None, &[12, 1, 2], "0.7")],
+            calendar(),
+            &FarmConfig::default(),
+        )
+        .unwrap();
+
+        // 2026-10-01 + 60 days is 2026-11-30
+        assert_eq!(seasons.milk(60), Quantity::from_whole(1));
+        assert_eq!(seasons.milk(61), "0.7".parse().unwrap());
+        assert_eq!(seasons.season(61).unwrap().name, "winter");
+    }
+
+    #[test]
+    fn test_no_seasons() {
+        let seasons = Seasons::default();
+        assert_eq!(seasons.season(5), None);
+        assert_eq!(seasons.milk(5), Quantity::from_whole(1));
+    }
+
+    #[test]
+    fn test_invalid_seasons() {
+        let farm = FarmConfig::default();
+        let invalid = [
+            (
+                vec![season("a", Some(0), &[], "1"), season("b", None, &[1], "1")],
+                calendar(),
+            ),
+            (vec![season("a", Some(100), &[], "1")], calendar()),
+            (vec![season("a", None, &[], "1")], calendar()),
+            (vec![season("a", None, &[1], "1")], Calendar::default()),
+            (vec![season("a", None, &[13], "1")], calendar()),
+            (vec![season("a", Some(0), &[], "10.001")], calendar()),
+        ];
+
+        for (seasons, calendar) in invalid {
+            assert!(
+                matches!(
+                    Seasons::new(&seasons, calendar, &farm),
+                    Err(YakShopError::InvalidParameter(_))
+                ),
+                "{seasons:?} should be invalid"
+            );
+        }
+    }
+}
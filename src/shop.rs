@@ -1,33 +1,54 @@
-use serde::Deserialize;
+use rand::Rng;
 use std::fmt::{self, Display, Formatter};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::{Products, Yak, YakShopError};
+use crate::config::FarmOverrides;
+use crate::yak::YakRecord;
+use crate::{
+    Expenses, HerdFormat, NaiveDate, Order, Products, ProfitAndLoss, Quantity, Sex, ShopConfig,
+    ShopDay, StochasticModel, Yak, YakShopError,
+};
 
-#[derive(Default, Deserialize, Debug, Clone)]
+#[derive(Default, Debug, Clone)]
 pub struct Shop {
-    #[serde(rename = "$value")]
     yaks: Vec<Yak>,
-    #[serde(skip_deserializing)]
     pub elapsed_days: u32,
-    #[serde(skip_deserializing)]
     pub produced_products: Products,
+    /// The costs of keeping the herd since the shop opened
+    pub expenses: Expenses,
+    /// The configuration the shop runs with, including the overrides from the herd file.
+    config: ShopConfig,
+    /// The farm settings from the herd file, kept so they are exported with the herd.
+    farm_overrides: FarmOverrides,
 }
 
 impl Display for Shop {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(date) = self.date() {
+            writeln!(f, "On {date} (day {}):", self.elapsed_days)?;
+        }
+
         write!(
             f,
-            r#"In Stock:
-    {:.3} liters of milk
+            r"In Stock:
+    {} liters of milk
     {} skins of wool
-Herd:"#,
+Herd:",
             self.produced_products.milk(),
             self.produced_products.wool()
         )?;
 
         for yak in &self.yaks {
             write!(f, "\n    {yak}")?;
+
+            if let Some(day) = yak.next_shave_shop_day() {
+                match self.config.calendar.date(day) {
+                    Some(date) => write!(f, " (next shave on day {day}, {date})")?,
+                    None => write!(f, " (next shave on day {day})")?,
+                }
+            }
         }
 
         Ok(())
@@ -38,30 +59,225 @@ impl TryFrom<&PathBuf> for Shop {
     type Error = YakShopError;
 
     fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
-        let herd_config = path.as_path();
-        if !herd_config.exists() {
-            return Err(YakShopError::ConfigFileNotFound(path.clone()));
-        }
+        Shop::from_path(path, None, &ShopConfig::default())
+    }
+}
 
-        let herd_xml =
-            std::fs::read_to_string(herd_config).expect("Could not read herd.xml file to string");
+impl FromStr for Shop {
+    type Err = YakShopError;
 
-        // TODO: These serde error messages could be a lot nicer
-        serde_xml_rs::from_str(&herd_xml)
-            .map_err(|err| YakShopError::ConfigFileParseError(format!("{err:?}")))
+    /// Parses a herd with the default configuration, detecting the format from the content.
+    fn from_str(herd: &str) -> Result<Self, Self::Err> {
+        HerdFormat::detect(herd).parse(herd, &ShopConfig::default())
     }
 }
 
 impl Shop {
-    pub fn step_days(&mut self, days: u32) {
+    /// Loads a herd file in the given format. When no format is given, it is determined from the
+    /// file extension, or failing that, from the content of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::ConfigFileNotFound` if the file does not exist,
+    /// `YakShopError::ConfigFileReadError` if it cannot be read,
+    /// `YakShopError::ConfigFileParseError` if it does not contain a valid herd, or
+    /// `YakShopError::InvalidParameter` if its farm settings are out of range.
+    pub fn from_path(
+        path: &Path,
+        format: Option<HerdFormat>,
+        config: &ShopConfig,
+    ) -> Result<Self, YakShopError> {
+        if !path.exists() {
+            return Err(YakShopError::ConfigFileNotFound(path.to_path_buf()));
+        }
+
+        let herd = std::fs::read_to_string(path).map_err(YakShopError::ConfigFileReadError)?;
+
+        let format = format
+            .or_else(|| HerdFormat::from_path(path))
+            .unwrap_or_else(|| HerdFormat::detect(&herd));
+
+        format.parse(&herd, config)
+    }
+
+    /// Reads a herd from `reader` in the given format, or detects the format from the content.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::ConfigFileReadError` if the reader fails, otherwise the same errors
+    /// as `Shop::from_path`.
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        format: Option<HerdFormat>,
+        config: &ShopConfig,
+    ) -> Result<Self, YakShopError> {
+        let mut herd = String::new();
+        reader
+            .read_to_string(&mut herd)
+            .map_err(YakShopError::ConfigFileReadError)?;
+
+        format
+            .unwrap_or_else(|| HerdFormat::detect(&herd))
+            .parse(&herd, config)
+    }
+
+    /// Serializes the herd as a herd.xml file, with the yaks at their current age. Yaks which have
+    /// died are left out unless `include_dead` is set.
+    #[must_use]
+    pub fn to_xml(&self, include_dead: bool) -> String {
+        let mut xml = String::from("<herd>\n");
+
+        if let Some(farm) = self.farm_overrides.to_xml() {
+            xml.push_str("  ");
+            xml.push_str(&farm);
+            xml.push('\n');
+        }
+
+        for yak in self
+            .yaks
+            .iter()
+            .filter(|yak| include_dead || yak.is_alive())
+        {
+            xml.push_str("  ");
+            xml.push_str(&yak.to_xml());
+            xml.push('\n');
+        }
+
+        xml.push_str("</herd>\n");
+        xml
+    }
+
+    pub(crate) fn from_records(
+        records: Vec<YakRecord>,
+        farm_overrides: FarmOverrides,
+        config: &ShopConfig,
+    ) -> Result<Self, YakShopError> {
+        let config = ShopConfig {
+            farm: farm_overrides.apply(&config.farm)?,
+            ..config.clone()
+        };
+
+        // The farm overrides can change the length of the yak year the seasons are placed on
+        let seasons = config.resolve_seasons()?;
+
+        let yaks = records
+            .into_iter()
+            .map(|record| Yak::from_record(record, &config.farm, &seasons))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Shop {
+            yaks,
+            config,
+            farm_overrides,
+            ..Shop::default()
+        })
+    }
+
+    pub(crate) fn farm_overrides(&self) -> FarmOverrides {
+        self.farm_overrides
+    }
+
+    /// The configuration the shop runs with, including any overrides from the herd file.
+    #[must_use]
+    pub fn config(&self) -> &ShopConfig {
+        &self.config
+    }
+
+    /// The current date of the shop, if the calendar has a start date.
+    #[must_use]
+    pub fn date(&self) -> Option<NaiveDate> {
+        self.config.calendar.date(self.elapsed_days)
+    }
+
+    /// The number of days since the shop opened on `day`, which can be a date if the calendar has
+    /// a start date.
+    ///
+    /// # Errors
+    ///
+    /// See `Calendar::day`.
+    pub fn day(&self, day: ShopDay) -> Result<u32, YakShopError> {
+        self.config.calendar.day(day)
+    }
+
+    /// Steps the shop forward by the given number of days, returning the products produced in that
+    /// time. The costs of keeping the yaks alive in that time are added to `expenses`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the stock, costs or elapsed days no longer fit, in which
+    /// case the shop is left in an inconsistent state and should be discarded.
+    pub fn step_days(&mut self, days: u32) -> Result<Products, YakShopError> {
+        let mut produced = Products::default();
+        let mut expenses = Expenses::default();
+
         for yak in &mut self.yaks {
+            let age = yak.age_in_days();
+
             // Add the products if the yak is still producing products
             if let Some(products) = yak.step_days(days) {
-                self.produced_products += products;
+                produced = produced.checked_add(&products)?;
             }
+
+            expenses = expenses.checked_add(&self.config.costs.upkeep(
+                &self.config.farm,
+                age,
+                yak.age_in_days() - age,
+            )?)?;
         }
 
-        self.elapsed_days += days;
+        self.record_production(days, &produced, &expenses)?;
+
+        Ok(produced)
+    }
+
+    /// Steps the shop using the stochastic production `model`. See `Yak::step_days_stochastic`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` under the same conditions as `step_days`.
+    pub fn step_days_stochastic<R: Rng>(
+        &mut self,
+        days: u32,
+        model: &StochasticModel,
+        rng: &mut R,
+    ) -> Result<Products, YakShopError> {
+        let mut produced = Products::default();
+        let mut expenses = Expenses::default();
+
+        for yak in &mut self.yaks {
+            let age = yak.age_in_days();
+
+            if let Some(products) = yak.step_days_stochastic(days, model, rng) {
+                produced = produced.checked_add(&products)?;
+            }
+
+            // Ill yaks still need feeding, only yaks which died early cost nothing
+            expenses = expenses.checked_add(&self.config.costs.upkeep(
+                &self.config.farm,
+                age,
+                yak.age_in_days() - age,
+            )?)?;
+        }
+
+        self.record_production(days, &produced, &expenses)?;
+
+        Ok(produced)
+    }
+
+    fn record_production(
+        &mut self,
+        days: u32,
+        produced: &Products,
+        expenses: &Expenses,
+    ) -> Result<(), YakShopError> {
+        self.produced_products = self.produced_products.checked_add(produced)?;
+        self.expenses = self.expenses.checked_add(expenses)?;
+        self.elapsed_days = self
+            .elapsed_days
+            .checked_add(days)
+            .ok_or_else(|| YakShopError::Overflow("elapsed days".to_string()))?;
+
+        Ok(())
     }
 
     #[must_use]
@@ -69,28 +285,157 @@ impl Shop {
         &self.yaks
     }
 
+    /// Finds a yak in the herd by name.
+    #[must_use]
+    pub fn yak(&self, name: &str) -> Option<&Yak> {
+        self.yaks.iter().find(|yak| yak.name() == name)
+    }
+
+    fn yak_mut(&mut self, name: &str) -> Result<&mut Yak, YakShopError> {
+        self.yaks
+            .iter_mut()
+            .find(|yak| yak.name() == name)
+            .ok_or_else(|| YakShopError::YakNotFound(name.to_string()))
+    }
+
+    /// Checks `name` can be given to a yak, as yaks are looked up by name.
+    fn check_new_name(&self, name: &str) -> Result<(), YakShopError> {
+        if name.trim().is_empty() {
+            return Err(YakShopError::InvalidParameter(
+                "A yak needs a name".to_string(),
+            ));
+        }
+        if self.yak(name).is_some() {
+            return Err(YakShopError::InvalidParameter(format!(
+                "There already is a yak named {name}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Adds a yak of `age` yak years to the herd, joining it today.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the name is empty or already taken, or the age
+    /// is out of range.
+    pub fn add_yak(
+        &mut self,
+        name: &str,
+        age: Quantity,
+        sex: Option<Sex>,
+    ) -> Result<&Yak, YakShopError> {
+        self.check_new_name(name)?;
+
+        let seasons = self.config.resolve_seasons()?;
+        let yak = Yak::new(
+            name,
+            age,
+            sex,
+            self.elapsed_days,
+            &self.config.farm,
+            &seasons,
+        )?;
+        self.yaks.push(yak);
+
+        Ok(&self.yaks[self.yaks.len() - 1])
+    }
+
+    /// Renames the yak called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::YakNotFound` if there is no such yak, or
+    /// `YakShopError::InvalidParameter` if the new name is empty or already taken.
+    pub fn rename_yak(&mut self, name: &str, new_name: &str) -> Result<&Yak, YakShopError> {
+        if name != new_name {
+            self.check_new_name(new_name)?;
+        }
+
+        let yak = self.yak_mut(name)?;
+        yak.set_name(new_name);
+        Ok(yak)
+    }
+
+    /// Corrects the age of the yak called `name` to `age` yak years.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::YakNotFound` if there is no such yak, or
+    /// `YakShopError::InvalidParameter` if the age is out of range.
+    pub fn set_yak_age(&mut self, name: &str, age: Quantity) -> Result<&Yak, YakShopError> {
+        let yak = self.yak_mut(name)?;
+        yak.set_year_age(age)?;
+        Ok(yak)
+    }
+
+    /// Removes the yak called `name` from the herd, returning it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::YakNotFound` if there is no such yak.
+    pub fn retire_yak(&mut self, name: &str) -> Result<Yak, YakShopError> {
+        let index = self
+            .yaks
+            .iter()
+            .position(|yak| yak.name() == name)
+            .ok_or_else(|| YakShopError::YakNotFound(name.to_string()))?;
+
+        Ok(self.yaks.remove(index))
+    }
+
+    /// Takes what it can of an order for `milk` and `wool` from the stock, so later orders can't
+    /// sell it again. Each product is either delivered in full or not at all.
     #[must_use]
     pub fn consume_products(
         &mut self,
-        milk: Option<f64>,
+        milk: Option<Quantity>,
         wool: Option<u32>,
-    ) -> (Option<f64>, Option<u32>) {
-        // A naive, but working approach of product consumption
-        let mut consumed_milk: Option<f64> = None;
-        if let Some(milk) = milk {
-            if self.produced_products.milk() >= milk {
-                consumed_milk = Some(milk);
-            }
-        }
+    ) -> (Option<Quantity>, Option<u32>) {
+        let (milk, wool) = self.produced_products.fill(milk, wool);
+        let consumed = Products::new(milk.unwrap_or_default(), wool.unwrap_or_default());
+        // SAFETY: `fill` only delivers what is in stock
+        self.produced_products = self
+            .produced_products
+            .checked_sub(&consumed)
+            .unwrap_or_default();
 
-        let mut consumed_wool: Option<u32> = None;
-        if let Some(wool) = wool {
-            if self.produced_products.wool() >= wool {
-                consumed_wool = Some(wool);
-            }
+        (milk, wool)
+    }
+
+    /// Records the products delivered for an order today, as returned by `consume_products`.
+    #[must_use]
+    pub fn order(&self, customer: &str, (milk, wool): (Option<Quantity>, Option<u32>)) -> Order {
+        Order {
+            customer: customer.to_string(),
+            day: self.elapsed_days,
+            delivered: Products::new(milk.unwrap_or_default(), wool.unwrap_or_default()),
         }
+    }
 
-        (consumed_milk, consumed_wool)
+    /// The profit and loss of the shop so far, with the revenue of the `orders` delivered up to
+    /// today at the configured prices. Orders for later days are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if the products sold, revenue or costs don't fit.
+    pub fn profit_and_loss(&self, orders: &[Order]) -> Result<ProfitAndLoss, YakShopError> {
+        let sold = orders
+            .iter()
+            .filter(|order| order.day <= self.elapsed_days)
+            .try_fold(Products::default(), |sold, order| {
+                sold.checked_add(&order.delivered)
+            })?;
+
+        // Make sure the total fits, so `ProfitAndLoss::costs` doesn't have to fail
+        self.expenses.total()?;
+
+        Ok(ProfitAndLoss {
+            revenue: self.config.prices.value(&sold)?,
+            sold,
+            expenses: self.expenses,
+        })
     }
 }
 
@@ -98,6 +443,10 @@ impl Shop {
 mod tests {
     use super::*;
 
+    fn liters(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
     fn fixtures_path() -> PathBuf {
         PathBuf::from("./tests/fixtures")
     }
@@ -130,25 +479,342 @@ mod tests {
         assert!(matches!(result, Err(YakShopError::ConfigFileNotFound(_))));
     }
 
+    #[test]
+    fn test_from_path_json() {
+        let herd = fixtures_path().join("valid_multi.json");
+        let shop = Shop::from_path(&herd, None, &ShopConfig::default()).unwrap();
+        assert_eq!(shop.yaks.len(), 3);
+    }
+
+    #[test]
+    fn test_from_path_yaml() {
+        let herd = fixtures_path().join("valid_multi.yaml");
+        let shop = Shop::from_path(&herd, None, &ShopConfig::default()).unwrap();
+        assert_eq!(shop.yaks.len(), 3);
+    }
+
+    #[test]
+    fn test_from_path_toml() {
+        let mut shop = Shop::from_path(
+            &fixtures_path().join("valid_multi.toml"),
+            None,
+            &ShopConfig::default(),
+        )
+        .unwrap();
+        shop.step_days(13).unwrap();
+
+        assert_eq!(shop.produced_products.milk(), liters("1104.48"));
+        assert_eq!(shop.produced_products.wool(), 3);
+    }
+
+    #[test]
+    fn test_from_path_explicit_format() {
+        let herd = fixtures_path().join("valid_multi.json");
+        let result = Shop::from_path(&herd, Some(HerdFormat::Xml), &ShopConfig::default());
+        assert!(matches!(result, Err(YakShopError::ConfigFileParseError(_))));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let shop: Shop = r#"<herd><labyak name="Betty-1" age="4" sex="f"/></herd>"#
+            .parse()
+            .unwrap();
+        assert_eq!(shop.yaks.len(), 1);
+
+        let shop: Shop = r#"{"herd": [{"name": "Betty-1", "age": 4}]}"#.parse().unwrap();
+        assert_eq!(shop.yaks.len(), 1);
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let herd = std::fs::File::open(fixtures_path().join("valid_multi.yaml")).unwrap();
+        let shop = Shop::from_reader(herd, Some(HerdFormat::Yaml), &ShopConfig::default()).unwrap();
+        assert_eq!(shop.yaks.len(), 3);
+    }
+
+    #[test]
+    fn test_from_reader_failure() {
+        struct BrokenReader;
+
+        impl Read for BrokenReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken pipe"))
+            }
+        }
+
+        let result = Shop::from_reader(BrokenReader, None, &ShopConfig::default());
+        assert!(matches!(result, Err(YakShopError::ConfigFileReadError(_))));
+    }
+
+    #[test]
+    fn test_to_xml_round_trip() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.step_days(13).unwrap();
+
+        let mut exported: Shop = shop.to_xml(true).parse().unwrap();
+        assert_eq!(exported.yaks.len(), 3);
+        for (yak, exported_yak) in shop.yaks.iter().zip(&exported.yaks) {
+            assert_eq!(yak.name(), exported_yak.name());
+            assert_eq!(yak.sex(), exported_yak.sex());
+            assert_ulps_eq!(yak.year_age(), exported_yak.year_age());
+            assert_ulps_eq!(
+                yak.year_age_last_shaved(),
+                exported_yak.year_age_last_shaved()
+            );
+        }
+
+        // The next season continues exactly where the previous one left off
+        assert_eq!(
+            exported.step_days(100).unwrap(),
+            shop.step_days(100).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_xml_without_dead() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.step_days(50).unwrap();
+
+        let exported: Shop = shop.to_xml(false).parse().unwrap();
+        assert_eq!(exported.yaks.len(), 2);
+        assert!(shop.to_xml(true).contains(r#"name="Betty-3" age="10""#));
+    }
+
+    #[test]
+    fn test_to_xml_keeps_farm_overrides() {
+        let herd = r#"<herd><farm max_yak_age="2000"/><labyak name="Betty-1" age="4"/></herd>"#;
+        let mut shop: Shop = herd.parse().unwrap();
+        shop.step_days(13).unwrap();
+
+        let xml = shop.to_xml(true);
+        assert!(xml.starts_with("<herd>\n  <farm max_yak_age=\"2000\"/>\n"));
+
+        let exported: Shop = xml.parse().unwrap();
+        assert_eq!(exported.config(), shop.config());
+    }
+
+    #[test]
+    fn test_to_xml_round_trip_with_long_years() {
+        let herd = r#"<herd><farm days_in_yak_year="365" max_yak_age="5000"/><labyak name="Betty-1" age="4" sex="f"/></herd>"#;
+        let mut shop: Shop = herd.parse().unwrap();
+        shop.step_days(2).unwrap();
+
+        let exported: Shop = shop.to_xml(true).parse().unwrap();
+        assert_eq!(exported.yaks[0].age_in_days(), 1462);
+        assert_eq!(
+            exported.yaks[0].age_last_shaved_in_days(),
+            shop.yaks[0].age_last_shaved_in_days()
+        );
+    }
+
+    #[test]
+    fn test_from_path_with_config() {
+        let config: ShopConfig = "[farm]\nmax_yak_age = 900".parse().unwrap();
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let shop = Shop::from_path(&herd_xml, None, &config).unwrap();
+
+        // Betty-3 is 9.5 years old, and so already dead
+        let alive: Vec<&str> = shop
+            .yaks()
+            .iter()
+            .filter(|yak| yak.is_alive())
+            .map(Yak::name)
+            .collect();
+        assert_eq!(alive, ["Betty-1", "Betty-2"]);
+    }
+
+    #[test]
+    fn test_display_with_calendar() {
+        let config: ShopConfig = "[calendar]\nstart_date = \"2026-10-01\"".parse().unwrap();
+        let herd = r#"<herd><labyak name="Betty-1" age="4" sex="f"/></herd>"#;
+        let mut shop = HerdFormat::Xml.parse(herd, &config).unwrap();
+
+        let day = shop.day("2026-10-14".parse().unwrap()).unwrap();
+        assert_eq!(day, 13);
+
+        shop.step_days(day).unwrap();
+        assert_eq!(shop.date(), Some("2026-10-14".parse().unwrap()));
+        assert_eq!(
+            shop.to_string(),
+            r"On 2026-10-14 (day 13):
+In Stock:
+    491.660 liters of milk
+    1 skins of wool
+Herd:
+    Betty-1 4.13 years old (next shave on day 13, 2026-10-14)"
+        );
+    }
+
+    #[test]
+    fn test_13_days_with_seasons() {
+        let config: ShopConfig = r#"
+            [[seasons]]
+            name = "lean"
+            start_day = 0
+            milk = 0.5
+            wool = 0.5
+            "#
+        .parse()
+        .unwrap();
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::from_path(&herd_xml, None, &config).unwrap();
+        shop.step_days(13).unwrap();
+
+        assert_eq!(shop.produced_products.milk(), liters("552.24"));
+        assert_eq!(shop.produced_products.wool(), 3);
+        // Betty-1 was shaved on day 0 and would usually be shaved again on day 13
+        assert_eq!(shop.yaks()[0].next_shave_shop_day(), Some(26));
+    }
+
     #[test]
     fn test_13_days() {
         let herd_xml = fixtures_path().join("valid_multi.xml");
         let mut shop = Shop::try_from(&herd_xml).unwrap();
-        shop.step_days(13);
+        shop.step_days(13).unwrap();
 
         assert_eq!(shop.elapsed_days, 13);
-        assert_ulps_eq!(shop.produced_products.milk(), 1104.480);
+        assert_eq!(shop.produced_products.milk(), liters("1104.48"));
         assert_eq!(shop.produced_products.wool(), 3);
     }
 
+    #[test]
+    fn test_step_days_returns_production() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        let first = shop.step_days(13).unwrap();
+        let second = shop.step_days(1).unwrap();
+
+        assert_eq!(second.wool(), 1);
+        assert_eq!(first + second, shop.produced_products);
+    }
+
+    #[test]
+    fn test_step_days_overflow() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.produced_products = Products::new(Quantity::ZERO, u32::MAX);
+
+        let result = shop.step_days(1);
+        assert!(matches!(result, Err(YakShopError::Overflow(_))));
+
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.elapsed_days = u32::MAX;
+
+        let result = shop.step_days(1);
+        assert!(matches!(result, Err(YakShopError::Overflow(_))));
+    }
+
+    #[test]
+    fn test_13_days_expenses() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.step_days(13).unwrap();
+
+        assert_eq!(shop.expenses.feed, liters("86.684"));
+        assert_eq!(shop.expenses.care, liters("19.5"));
+
+        // Betty-3 dies on day 50, and so only costs for 37 more days
+        shop.step_days(100).unwrap();
+        assert_eq!(
+            shop.expenses.care,
+            liters("19.5") + liters("100") + liters("18.5")
+        );
+    }
+
+    #[test]
+    fn test_profit_and_loss() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.step_days(13).unwrap();
+
+        let consumed = shop.consume_products(Some(liters("1100")), Some(3));
+        let orders = [
+            shop.order("Medvedev", consumed),
+            Order {
+                day: 14,
+                ..shop.order("Dmitry", consumed)
+            },
+        ];
+
+        // What was sold can't be sold again
+        assert_eq!(shop.produced_products, Products::new(liters("4.48"), 0));
+        assert_eq!(
+            shop.consume_products(Some(liters("1100")), Some(3)),
+            (None, None)
+        );
+
+        let pnl = shop.profit_and_loss(&orders).unwrap();
+        assert_eq!(pnl.sold, Products::new(liters("1100"), 3));
+        assert_eq!(pnl.revenue, liters("1440"));
+        assert_eq!(pnl.costs(), liters("106.184"));
+        assert_ulps_eq!(pnl.profit(), 1333.816);
+    }
+
+    #[test]
+    fn test_edit_herd() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+
+        let yak = shop
+            .add_yak("Betty-4", liters("2.5"), Some(Sex::Female))
+            .unwrap();
+        assert_eq!(yak.age_in_days(), 250);
+        assert!(matches!(
+            shop.add_yak("Betty-1", liters("1"), None),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            shop.add_yak(" ", liters("1"), None),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+
+        shop.rename_yak("Betty-4", "Dolma").unwrap();
+        shop.set_yak_age("Dolma", liters("3")).unwrap();
+        assert_eq!(shop.yak("Dolma").unwrap().age_in_days(), 300);
+        assert!(matches!(
+            shop.rename_yak("Dolma", "Betty-2"),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+
+        let retired = shop.retire_yak("Betty-3").unwrap();
+        assert_eq!(retired.name(), "Betty-3");
+        assert!(matches!(
+            shop.retire_yak("Betty-3"),
+            Err(YakShopError::YakNotFound(_))
+        ));
+
+        let names: Vec<&str> = shop.yaks().iter().map(Yak::name).collect();
+        assert_eq!(names, ["Betty-1", "Betty-2", "Dolma"]);
+    }
+
+    #[test]
+    fn test_add_yak_later() {
+        let herd_xml = fixtures_path().join("valid_multi.xml");
+        let mut shop = Shop::try_from(&herd_xml).unwrap();
+        shop.step_days(100).unwrap();
+
+        let yak = shop.add_yak("Dolma", liters("2.5"), None).unwrap();
+        assert_eq!(yak.next_shave_shop_day(), Some(100));
+
+        shop.step_days(1).unwrap();
+        let yak = shop.yak("Dolma").unwrap();
+        assert_eq!(yak.shave_days(), [100]);
+        assert_eq!(
+            yak.next_shave_shop_day(),
+            Some(101 + yak.days_until_next_shave().unwrap())
+        );
+    }
+
     #[test]
     fn test_14_days() {
         let herd_xml = fixtures_path().join("valid_multi.xml");
         let mut shop = Shop::try_from(&herd_xml).unwrap();
-        shop.step_days(14);
+        shop.step_days(14).unwrap();
 
         assert_eq!(shop.elapsed_days, 14);
-        assert_ulps_eq!(shop.produced_products.milk(), 1188.810);
+        assert_eq!(shop.produced_products.milk(), liters("1188.81"));
         assert_eq!(shop.produced_products.wool(), 4);
     }
 }
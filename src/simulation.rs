@@ -0,0 +1,325 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::{self, Display, Formatter};
+
+use crate::{Quantity, Shop, YakShopError};
+
+/// Parameters for the stochastic production model.
+///
+/// All rates are daily probabilities, and are applied independently to every living yak.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StochasticModel {
+    /// The maximum relative deviation of the daily milk yield, e.g. `0.1` = +/- 10%.
+    pub milk_noise: f64,
+    /// The chance of a yak being ill on any given day. Ill yaks produce nothing for the day.
+    pub illness_rate: f64,
+    /// The chance of a yak dying on any given day before reaching its maximum age.
+    pub mortality_rate: f64,
+}
+
+impl Default for StochasticModel {
+    fn default() -> Self {
+        StochasticModel {
+            milk_noise: 0.1,
+            illness_rate: 0.01,
+            mortality_rate: 0.000_5,
+        }
+    }
+}
+
+impl StochasticModel {
+    /// Ensures all parameters are within the range `[0.0, 1.0]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` naming the first parameter out of range.
+    pub fn validate(&self) -> Result<(), YakShopError> {
+        for (name, value) in [
+            ("milk_noise", self.milk_noise),
+            ("illness_rate", self.illness_rate),
+            ("mortality_rate", self.mortality_rate),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(YakShopError::InvalidParameter(format!(
+                    "{name} must be between 0 and 1, got {value}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn dies<R: Rng>(&self, rng: &mut R) -> bool {
+        rng.gen_bool(self.mortality_rate)
+    }
+
+    pub(crate) fn is_ill<R: Rng>(&self, rng: &mut R) -> bool {
+        rng.gen_bool(self.illness_rate)
+    }
+
+    pub(crate) fn milk_yield<R: Rng>(&self, milk: Quantity, rng: &mut R) -> Quantity {
+        if self.milk_noise == 0.0 {
+            return milk;
+        }
+
+        let factor = 1.0 + rng.gen_range(-self.milk_noise..=self.milk_noise);
+
+        // SAFETY: factor is within [0.0, 2.0], so the result is always a valid quantity unless the
+        // daily yield is absurdly large.
+        Quantity::try_from_f64(milk.to_f64() * factor).unwrap_or(milk)
+    }
+}
+
+/// A set of samples from a number of simulation runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// Always kept sorted so percentiles can be read directly.
+    samples: Vec<f64>,
+}
+
+impl Distribution {
+    fn new(mut samples: Vec<f64>) -> Self {
+        samples.sort_by(f64::total_cmp);
+        Distribution { samples }
+    }
+
+    #[must_use]
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let count = self.samples.len() as f64;
+        self.samples.iter().sum::<f64>() / count
+    }
+
+    /// The nearest-rank percentile of the samples, where `percentile` is within `[0, 100]`.
+    #[must_use]
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+
+        let percentile = percentile.clamp(0.0, 100.0);
+
+        // SAFETY: The rank is within [0, len] due to the clamp above.
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        #[allow(clippy::cast_precision_loss)]
+        let rank = ((percentile / 100.0) * self.samples.len() as f64).ceil() as usize;
+
+        self.samples[rank.saturating_sub(1)]
+    }
+}
+
+impl Display for Distribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.3}, p5 {:.3}, p50 {:.3}, p95 {:.3}",
+            self.mean(),
+            self.percentile(5.0),
+            self.percentile(50.0),
+            self.percentile(95.0)
+        )
+    }
+}
+
+/// The outcome of running a Monte Carlo simulation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub seed: u64,
+    pub runs: u32,
+    pub days: u32,
+    pub milk: Distribution,
+    pub wool: Distribution,
+    pub surviving_yaks: Distribution,
+}
+
+impl Display for SimulationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r"Simulated {} runs of {} days (seed {}):
+    Milk (liters): {}
+    Wool (skins): {}
+    Surviving yaks: {}",
+            self.runs, self.days, self.seed, self.milk, self.wool, self.surviving_yaks
+        )
+    }
+}
+
+/// The most simulation runs a `MonteCarlo` does.
+pub const MAX_RUNS: u32 = 10_000;
+
+/// The most days a `MonteCarlo` simulates a shop for.
+pub const MAX_DAYS: u32 = 10_000;
+
+/// Runs a number of seeded stochastic simulations of a shop.
+#[derive(Debug, Clone, Copy)]
+pub struct MonteCarlo {
+    model: StochasticModel,
+    runs: u32,
+    seed: u64,
+}
+
+impl MonteCarlo {
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the model is invalid, or no runs or more than
+    /// `MAX_RUNS` are requested.
+    pub fn new(model: StochasticModel, runs: u32, seed: u64) -> Result<Self, YakShopError> {
+        model.validate()?;
+
+        if runs == 0 {
+            return Err(YakShopError::InvalidParameter(
+                "At least one simulation run is required".to_string(),
+            ));
+        }
+        if runs > MAX_RUNS {
+            return Err(YakShopError::InvalidParameter(format!(
+                "At most {MAX_RUNS} simulation runs can be requested"
+            )));
+        }
+
+        Ok(MonteCarlo { model, runs, seed })
+    }
+
+    /// Simulates the shop for the given number of days, `runs` times over. The provided shop is
+    /// left untouched, each run starts from a copy of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if more than `MAX_DAYS` days are requested, or
+    /// `YakShopError::Overflow` if the stock of any run overflows.
+    pub fn run(&self, shop: &Shop, days: u32) -> Result<SimulationReport, YakShopError> {
+        if days > MAX_DAYS {
+            return Err(YakShopError::InvalidParameter(format!(
+                "A shop can be simulated for at most {MAX_DAYS} days"
+            )));
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let mut milk = Vec::with_capacity(self.runs as usize);
+        let mut wool = Vec::with_capacity(self.runs as usize);
+        let mut surviving_yaks = Vec::with_capacity(self.runs as usize);
+
+        for _ in 0..self.runs {
+            let mut shop = shop.clone();
+            shop.step_days_stochastic(days, &self.model, &mut rng)?;
+
+            milk.push(shop.produced_products.milk().to_f64());
+            wool.push(f64::from(shop.produced_products.wool()));
+
+            #[allow(clippy::cast_precision_loss)]
+            surviving_yaks.push(shop.yaks().iter().filter(|yak| yak.is_alive()).count() as f64);
+        }
+
+        Ok(SimulationReport {
+            seed: self.seed,
+            runs: self.runs,
+            days,
+            milk: Distribution::new(milk),
+            wool: Distribution::new(wool),
+            surviving_yaks: Distribution::new(surviving_yaks),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn shop() -> Shop {
+        Shop::try_from(&PathBuf::from("./tests/fixtures/valid_multi.xml")).unwrap()
+    }
+
+    fn quiet_model() -> StochasticModel {
+        StochasticModel {
+            milk_noise: 0.0,
+            illness_rate: 0.0,
+            mortality_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_invalid_model() {
+        let model = StochasticModel {
+            illness_rate: 1.5,
+            ..StochasticModel::default()
+        };
+        let result = MonteCarlo::new(model, 10, 0);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_zero_runs() {
+        let result = MonteCarlo::new(StochasticModel::default(), 0, 0);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_limits() {
+        let result = MonteCarlo::new(StochasticModel::default(), MAX_RUNS + 1, 0);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+
+        let simulation = MonteCarlo::new(StochasticModel::default(), 1, 0).unwrap();
+        let result = simulation.run(&shop(), MAX_DAYS + 1);
+        assert!(matches!(result, Err(YakShopError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_quiet_model_matches_deterministic() {
+        let report = MonteCarlo::new(quiet_model(), 5, 42)
+            .unwrap()
+            .run(&shop(), 13)
+            .unwrap();
+
+        assert_ulps_eq!(report.milk.mean(), 1104.480);
+        assert_ulps_eq!(report.milk.percentile(5.0), 1104.480);
+        assert_ulps_eq!(report.wool.mean(), 3.0);
+        assert_ulps_eq!(report.surviving_yaks.mean(), 3.0);
+    }
+
+    #[test]
+    fn test_same_seed_same_report() {
+        let simulation = MonteCarlo::new(StochasticModel::default(), 20, 7).unwrap();
+        assert_eq!(
+            simulation.run(&shop(), 100).unwrap(),
+            simulation.run(&shop(), 100).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_certain_death() {
+        let model = StochasticModel {
+            mortality_rate: 1.0,
+            ..quiet_model()
+        };
+        let report = MonteCarlo::new(model, 3, 0)
+            .unwrap()
+            .run(&shop(), 13)
+            .unwrap();
+
+        assert_ulps_eq!(report.milk.mean(), 0.0);
+        assert_ulps_eq!(report.surviving_yaks.percentile(95.0), 0.0);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let distribution = Distribution::new((1..=100).map(f64::from).rev().collect());
+        assert_ulps_eq!(distribution.percentile(0.0), 1.0);
+        assert_ulps_eq!(distribution.percentile(5.0), 5.0);
+        assert_ulps_eq!(distribution.percentile(50.0), 50.0);
+        assert_ulps_eq!(distribution.percentile(100.0), 100.0);
+        assert_ulps_eq!(distribution.mean(), 50.5);
+    }
+}
@@ -1,35 +1,64 @@
-use serde::Deserialize;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
 mod products;
 mod serialization;
 
 pub use products::Products;
-use products::{yak_can_produce_wool, yak_milk_production};
-use serialization::yak_float_years_to_days;
+use products::{yak_milk_production, yak_next_shave_day, yak_seasonal_next_shave_day};
+pub(crate) use serialization::YakRecord;
+use serialization::{xml_escape, yak_days_to_float_years, yak_days_to_years, yak_years_to_days};
+
+use crate::{FarmConfig, Quantity, Seasons, StochasticModel, YakShopError};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sex {
+    #[serde(rename = "f", alias = "female")]
+    Female,
+    #[serde(rename = "m", alias = "male")]
+    Male,
+}
 
-/// The number of days in a yak year.
-const DAYS_IN_YAK_YEAR: f64 = 100.0;
-/// A yak lives for 10 years, there are 100 days in a yak year.
-const MAX_YAK_AGE: u32 = 1_000;
+impl Display for Sex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Sex::Female => write!(f, "f"),
+            Sex::Male => write!(f, "m"),
+        }
+    }
+}
 
-#[derive(Deserialize, Debug, Clone)]
-#[allow(never_read)]
+#[derive(Debug, Clone)]
 pub struct Yak {
     name: String,
-    /// The age of the yak in yak years
-    #[serde(deserialize_with = "yak_float_years_to_days")]
+    /// The age of the yak in days
     age: u32,
-    /// The age of the yak when it was last shaved
-    #[serde(skip_deserializing)]
+    sex: Option<Sex>,
+    /// The age of the yak (in days) when it was last shaved
     age_last_shaved: u32,
+    /// The age at which the yak can next be shaved. Computed when the yak is shaved, or on first
+    /// use for yaks which have not been shaved since they were loaded.
+    next_shave_day: Option<u32>,
+    /// Whether the yak died before reaching `FarmConfig::max_yak_age`
+    deceased: bool,
+    /// The number of days the yak has been alive in the shop
+    days_in_shop: u32,
+    /// The products the yak has produced since the shop opened
+    produced_products: Products,
+    /// The shop days on which the yak was shaved
+    shave_days: Vec<u32>,
+    /// The farm the yak lives on, which determines how it ages and produces
+    farm: FarmConfig,
+    /// The seasons of the shop, which change how much the yak produces on each day
+    seasons: Seasons,
 }
 
 impl Display for Yak {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{} {} years old", self.name, self.year_age())?;
 
-        if self.age >= MAX_YAK_AGE {
+        if !self.is_alive() {
             write!(f, " (dead)")?;
         }
 
@@ -38,7 +67,118 @@ impl Display for Yak {
 }
 
 impl Yak {
+    /// Creates a yak from a herd file entry, converting its ages to days for the given `farm`.
+    pub(crate) fn from_record(
+        record: YakRecord,
+        farm: &FarmConfig,
+        seasons: &Seasons,
+    ) -> Result<Self, YakShopError> {
+        Ok(Yak {
+            age: yak_years_to_days(farm, record.age)?,
+            age_last_shaved: yak_years_to_days(farm, record.age_last_shaved)?,
+            name: record.name,
+            sex: record.sex,
+            next_shave_day: None,
+            deceased: record.deceased,
+            days_in_shop: 0,
+            produced_products: Products::default(),
+            shave_days: Vec::new(),
+            farm: *farm,
+            seasons: seasons.clone(),
+        })
+    }
+
+    /// Creates a yak which joins the herd at `age` yak years old, `days_in_shop` days after the
+    /// shop opened.
+    pub(crate) fn new(
+        name: &str,
+        age: Quantity,
+        sex: Option<Sex>,
+        days_in_shop: u32,
+        farm: &FarmConfig,
+        seasons: &Seasons,
+    ) -> Result<Self, YakShopError> {
+        let record = YakRecord {
+            name: name.to_string(),
+            age,
+            sex,
+            age_last_shaved: Quantity::ZERO,
+            deceased: false,
+        };
+
+        let yak = Yak::from_record(record, farm, seasons).map_err(|_| too_old(age))?;
+        Ok(Yak {
+            days_in_shop,
+            ..yak
+        })
+    }
+
+    /// The yak as a herd file entry, with its current ages.
+    pub(crate) fn to_record(&self) -> YakRecord {
+        YakRecord {
+            name: self.name.clone(),
+            age: yak_days_to_years(&self.farm, self.age),
+            sex: self.sex,
+            age_last_shaved: yak_days_to_years(&self.farm, self.age_last_shaved),
+            deceased: self.deceased,
+        }
+    }
+
+    pub(crate) fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    /// Corrects the age of the yak to `age` yak years. If the yak was last shaved when it was
+    /// older than that, it counts as shaved today.
+    pub(crate) fn set_year_age(&mut self, age: Quantity) -> Result<(), YakShopError> {
+        self.age = yak_years_to_days(&self.farm, age).map_err(|_| too_old(age))?;
+        self.age_last_shaved = self.age_last_shaved.min(self.age);
+        self.next_shave_day = None;
+
+        Ok(())
+    }
+
     pub fn step_days(&mut self, days: u32) -> Option<Products> {
+        self.step(days, |yak, products| {
+            products.add_milk(yak.milk_production());
+            if yak.age >= yak.next_shave_day() {
+                yak.shave();
+                products.add_wool(1);
+            }
+        })
+    }
+
+    /// Like `step_days`, but every day the yak may die early, fall ill and produce nothing, or
+    /// produce more or less milk than usual according to the `model`.
+    pub fn step_days_stochastic<R: Rng>(
+        &mut self,
+        days: u32,
+        model: &StochasticModel,
+        rng: &mut R,
+    ) -> Option<Products> {
+        self.step(days, |yak, products| {
+            if model.dies(rng) {
+                yak.deceased = true;
+                return;
+            }
+
+            if !model.is_ill(rng) {
+                products.add_milk(model.milk_yield(yak.milk_production(), rng));
+                if yak.age >= yak.next_shave_day() {
+                    yak.shave();
+                    products.add_wool(1);
+                }
+            }
+        })
+    }
+
+    /// Steps the yak through `days` days, or until it dies, with `produce` adding what it produces
+    /// each day. Returns the products of the days, or `None` if the yak was already dead.
+    fn step(
+        &mut self,
+        days: u32,
+        mut produce: impl FnMut(&mut Self, &mut Products),
+    ) -> Option<Products> {
         // Return early if nothing to do
         if !self.is_alive() {
             return None;
@@ -51,21 +191,45 @@ impl Yak {
             if !self.is_alive() {
                 break;
             }
-            products.add_milk(yak_milk_production(self.age));
-            if yak_can_produce_wool(self.age, self.age_last_shaved) {
-                self.age_last_shaved = self.age;
-                products.add_wool(1);
+            produce(self, &mut products);
+            if !self.is_alive() {
+                break;
             }
 
             self.age += 1;
+            self.days_in_shop += 1;
         }
 
+        // SAFETY: A single yak produces at most 1,000,000 liters of milk (see
+        // `FarmConfig::validate` and `Seasons::new`) and 1 wool a day, so its lifetime totals
+        // can't overflow even if it were to live for `u32::MAX` days.
+        self.produced_products += products.clone();
         Some(products)
     }
 
+    /// The milk the yak produces today, in the current season.
+    fn milk_production(&self) -> Quantity {
+        let milk = yak_milk_production(&self.farm, self.age);
+
+        // SAFETY: The base production and seasonal multipliers are bounded by validation, so this
+        // can't overflow.
+        milk.checked_scale(self.seasons.milk(self.days_in_shop))
+            .unwrap_or(milk)
+    }
+
+    fn shave(&mut self) {
+        self.age_last_shaved = self.age;
+        self.next_shave_day = Some(yak_seasonal_next_shave_day(
+            &self.farm,
+            self.age,
+            self.seasons.wool(self.days_in_shop),
+        ));
+        self.shave_days.push(self.days_in_shop);
+    }
+
     #[must_use]
     pub fn is_alive(&self) -> bool {
-        self.age < MAX_YAK_AGE
+        !self.deceased && self.age < self.farm.max_yak_age
     }
 
     #[must_use]
@@ -75,25 +239,154 @@ impl Yak {
 
     #[must_use]
     pub fn year_age(&self) -> f64 {
-        f64::from(self.age) / DAYS_IN_YAK_YEAR
+        yak_days_to_float_years(&self.farm, self.age)
+    }
+
+    /// The age of the yak in days.
+    #[must_use]
+    pub fn age_in_days(&self) -> u32 {
+        self.age
     }
 
     #[must_use]
     pub fn year_age_last_shaved(&self) -> f64 {
-        f64::from(self.age_last_shaved) / DAYS_IN_YAK_YEAR
+        yak_days_to_float_years(&self.farm, self.age_last_shaved)
+    }
+
+    /// The age of the yak in days when it was last shaved.
+    #[must_use]
+    pub fn age_last_shaved_in_days(&self) -> u32 {
+        self.age_last_shaved
+    }
+
+    #[must_use]
+    pub fn sex(&self) -> Option<Sex> {
+        self.sex
+    }
+
+    /// The age (in days) from which the yak is eligible to be shaved again.
+    #[must_use]
+    pub fn next_shave_day(&self) -> u32 {
+        self.next_shave_day
+            .unwrap_or_else(|| yak_next_shave_day(&self.farm, self.age_last_shaved))
+    }
+
+    /// The number of days until the yak can be shaved again, or `None` if it will not live that
+    /// long.
+    #[must_use]
+    pub fn days_until_next_shave(&self) -> Option<u32> {
+        let next_shave_day = self.next_shave_day();
+        if !self.is_alive() || next_shave_day >= self.farm.max_yak_age {
+            return None;
+        }
+
+        Some(next_shave_day.saturating_sub(self.age))
+    }
+
+    /// The shop day on which the yak can be shaved again, or `None` if it will not live that long.
+    #[must_use]
+    pub fn next_shave_shop_day(&self) -> Option<u32> {
+        self.days_until_next_shave()
+            .map(|days| self.days_in_shop + days)
     }
+
+    /// The products this yak has produced since the shop opened.
+    #[must_use]
+    pub fn produced_products(&self) -> &Products {
+        &self.produced_products
+    }
+
+    /// The days since the shop opened on which this yak was shaved.
+    #[must_use]
+    pub fn shave_days(&self) -> &[u32] {
+        &self.shave_days
+    }
+
+    /// The number of days until the yak dies of old age, or 0 if it is already dead.
+    #[must_use]
+    pub fn days_remaining(&self) -> u32 {
+        if self.is_alive() {
+            self.farm.max_yak_age - self.age
+        } else {
+            0
+        }
+    }
+
+    /// The products the yak will produce for the rest of its life.
+    #[must_use]
+    pub fn projected_products(&self) -> Products {
+        self.clone()
+            .step_days(self.days_remaining())
+            .unwrap_or_default()
+    }
+
+    /// Serializes the yak as a `labyak` element of a herd.xml file. The shave state (and early
+    /// death) is kept in extra attributes, so the yak can continue where it left off when loaded.
+    pub(crate) fn to_xml(&self) -> String {
+        let mut attributes = vec![
+            format!(r#"name="{}""#, xml_escape(&self.name)),
+            format!(
+                r#"age="{}""#,
+                yak_days_to_years(&self.farm, self.age).to_f64()
+            ),
+        ];
+
+        if let Some(sex) = self.sex {
+            attributes.push(format!(r#"sex="{sex}""#));
+        }
+        if self.age_last_shaved > 0 {
+            attributes.push(format!(
+                r#"age_last_shaved="{}""#,
+                yak_days_to_years(&self.farm, self.age_last_shaved).to_f64()
+            ));
+        }
+        if self.deceased {
+            attributes.push(r#"deceased="true""#.to_string());
+        }
+
+        format!("<labyak {}/>", attributes.join(" "))
+    }
+}
+
+fn too_old(age: Quantity) -> YakShopError {
+    YakShopError::InvalidParameter(format!("{age} years is too old for a yak"))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::products::MIN_YAK_SHAVE_AGE;
     use super::*;
+    use crate::Quantity;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// The default `FarmConfig::min_shave_age`
+    const MIN_SHAVE_AGE: u32 = 100;
+
+    fn liters(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
+    fn model(milk_noise: f64, illness_rate: f64, mortality_rate: f64) -> StochasticModel {
+        StochasticModel {
+            milk_noise,
+            illness_rate,
+            mortality_rate,
+        }
+    }
 
     fn default_yak() -> Yak {
         Yak {
             name: "Yak".to_string(),
             age: 0,
+            sex: None,
             age_last_shaved: 0,
+            next_shave_day: None,
+            deceased: false,
+            days_in_shop: 0,
+            produced_products: Products::default(),
+            shave_days: Vec::new(),
+            farm: FarmConfig::default(),
+            seasons: Seasons::default(),
         }
     }
 
@@ -121,10 +414,131 @@ mod tests {
     #[test]
     fn test_display_dead() {
         let mut yak = default_yak();
-        yak.age = MAX_YAK_AGE;
+        yak.age = yak.farm.max_yak_age;
         assert_eq!(format!("{yak}"), "Yak 10 years old (dead)");
     }
 
+    #[test]
+    fn test_display_deceased() {
+        let mut yak = default_yak();
+        yak.deceased = true;
+        assert_eq!(format!("{yak}"), "Yak 0 years old (dead)");
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let mut yak = default_yak();
+        yak.name = "Betty <1> & \"Co\"".to_string();
+        yak.age = 413;
+        yak.sex = Some(Sex::Female);
+        assert_eq!(
+            yak.to_xml(),
+            r#"<labyak name="Betty &lt;1&gt; &amp; &quot;Co&quot;" age="4.13" sex="f"/>"#
+        );
+
+        yak.age_last_shaved = 405;
+        yak.deceased = true;
+        assert_eq!(
+            yak.to_xml(),
+            r#"<labyak name="Betty &lt;1&gt; &amp; &quot;Co&quot;" age="4.13" sex="f" age_last_shaved="4.05" deceased="true"/>"#
+        );
+    }
+
+    #[test]
+    fn test_set_year_age() {
+        let mut yak = default_yak();
+        yak.age = 400;
+        yak.age_last_shaved = 400;
+        yak.next_shave_day = Some(413);
+
+        yak.set_year_age(liters("3.5")).unwrap();
+        assert_eq!(yak.age, 350);
+        assert_eq!(yak.age_last_shaved, 350);
+        assert_eq!(yak.next_shave_day(), 362);
+
+        assert!(matches!(
+            yak.set_year_age(Quantity::from_whole(u64::MAX / 1000)),
+            Err(YakShopError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_record() {
+        let mut yak = default_yak();
+        yak.age = 413;
+        yak.age_last_shaved = 405;
+
+        let record = yak.to_record();
+        assert_eq!(record.age, liters("4.13"));
+        assert_eq!(record.age_last_shaved, liters("4.05"));
+
+        // Ages which aren't a whole number of thousandths of a year round up, so they load back
+        // to the same number of days
+        yak.farm.days_in_yak_year = 365;
+        yak.age = 2;
+        assert_eq!(yak.to_record().age, liters("0.006"));
+        let yak = Yak::from_record(yak.to_record(), &yak.farm, &Seasons::default()).unwrap();
+        assert_eq!(yak.age, 2);
+    }
+
+    #[test]
+    fn test_next_shave_day() {
+        let mut yak = default_yak();
+        assert_eq!(yak.next_shave_day(), MIN_SHAVE_AGE);
+        assert_eq!(yak.days_until_next_shave(), Some(MIN_SHAVE_AGE));
+
+        yak.age = MIN_SHAVE_AGE;
+        yak.step_days(1);
+        assert_eq!(yak.next_shave_day, Some(110));
+        assert_eq!(yak.days_until_next_shave(), Some(9));
+        assert_eq!(yak.next_shave_shop_day(), Some(10));
+    }
+
+    #[test]
+    fn test_no_next_shave_before_death() {
+        let mut yak = default_yak();
+        yak.age = 995;
+        yak.age_last_shaved = 990;
+        assert_eq!(yak.days_until_next_shave(), None);
+        assert_eq!(yak.next_shave_shop_day(), None);
+    }
+
+    #[test]
+    fn test_lifetime_accounting() {
+        let mut yak = default_yak();
+        yak.age = MIN_SHAVE_AGE;
+
+        let first = yak.step_days(5).unwrap();
+        let second = yak.step_days(6).unwrap();
+
+        assert_eq!(yak.produced_products(), &(first + second));
+        assert_eq!(yak.shave_days(), &[0, 10]);
+    }
+
+    #[test]
+    fn test_days_remaining() {
+        let mut yak = default_yak();
+        yak.age = 990;
+        assert_eq!(yak.days_remaining(), 10);
+
+        yak.step_days(20);
+        assert_eq!(yak.days_remaining(), 0);
+    }
+
+    #[test]
+    fn test_projected_products() {
+        let mut yak = default_yak();
+        yak.age = 998;
+
+        assert_eq!(yak.projected_products(), Products::new(liters("40.09"), 1));
+        // Projection does not step the yak itself
+        assert_eq!(yak.age, 998);
+        assert_eq!(yak.produced_products(), &Products::default());
+
+        yak.step_days(2);
+        assert_eq!(yak.projected_products(), Products::default());
+    }
+
     #[test]
     fn test_step_until_death() {
         let mut yak = default_yak();
@@ -166,14 +580,14 @@ mod tests {
 
         let result = yak.step_days(1);
         assert_eq!(yak.age, 999);
-        assert_eq!(result, Some(Products::new(2006, 1)));
+        assert_eq!(result, Some(Products::new(liters("20.06"), 1)));
 
         // Now step by many days.
         // The Yak should still produce milk for the last day it is alive, but nothing after that.
         let result = yak.step_days(100);
         assert_eq!(yak.age, 1000);
         assert!(!yak.is_alive());
-        assert_eq!(result, Some(Products::new(2003, 0)));
+        assert_eq!(result, Some(Products::new(liters("20.03"), 0)));
     }
 
     #[test]
@@ -182,18 +596,18 @@ mod tests {
         let products = yak.step_days(1).unwrap();
 
         assert_eq!(yak.age, 1);
-        assert_ulps_eq!(products.milk(), 50.0);
+        assert_eq!(products.milk(), liters("50"));
         assert_eq!(products.wool(), 0);
     }
 
     #[test]
     fn test_step_days_milk_and_wool() {
         let mut yak = default_yak();
-        yak.age = MIN_YAK_SHAVE_AGE;
+        yak.age = MIN_SHAVE_AGE;
         let products = yak.step_days(1).unwrap();
 
-        assert_eq!(yak.age, MIN_YAK_SHAVE_AGE + 1);
-        assert_ulps_eq!(products.milk(), 47.0);
+        assert_eq!(yak.age, MIN_SHAVE_AGE + 1);
+        assert_eq!(products.milk(), liters("47"));
         assert_eq!(products.wool(), 1);
     }
 
@@ -203,32 +617,82 @@ mod tests {
         let products = yak.step_days(2).unwrap();
 
         assert_eq!(yak.age, 2);
-        assert_ulps_eq!(products.milk(), 50.0 + 49.97);
+        assert_eq!(products.milk(), liters("99.97"));
         assert_eq!(products.wool(), 0);
     }
 
     #[test]
     fn test_two_days_milk_and_wool() {
         let mut yak = default_yak();
-        yak.age = MIN_YAK_SHAVE_AGE;
+        yak.age = MIN_SHAVE_AGE;
         let products = yak.step_days(2).unwrap();
 
-        assert_eq!(yak.age, MIN_YAK_SHAVE_AGE + 2);
-        assert_ulps_eq!(products.milk(), 47.0 + 46.97);
+        assert_eq!(yak.age, MIN_SHAVE_AGE + 2);
+        assert_eq!(products.milk(), liters("93.97"));
         assert_eq!(products.wool(), 1);
     }
 
     #[test]
     fn test_double_wool() {
         let mut yak = default_yak();
-        yak.age = MIN_YAK_SHAVE_AGE;
+        yak.age = MIN_SHAVE_AGE;
         // First shave = Yak age 100. (last shave = 0)
         // Second shave = Yak age 110. (last shave = 100, 8 + 109 * 0.01 = 9.09, there 109 is to
         //                soon to shave again)
         // Therefore need 11 days to tick from day 100 to 110 to completion for 2 wools.
         let products = yak.step_days(11).unwrap();
 
-        assert_eq!(yak.age, MIN_YAK_SHAVE_AGE + 11);
+        assert_eq!(yak.age, MIN_SHAVE_AGE + 11);
         assert_eq!(products.wool(), 2);
     }
+
+    #[test]
+    fn test_no_noise_matches_deterministic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut stochastic = default_yak();
+        let mut deterministic = default_yak();
+        stochastic.age = 98;
+        deterministic.age = 98;
+
+        assert_eq!(
+            stochastic.step_days_stochastic(20, &model(0.0, 0.0, 0.0), &mut rng),
+            deterministic.step_days(20)
+        );
+    }
+
+    #[test]
+    fn test_always_ill() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut yak = default_yak();
+        let products = yak.step_days_stochastic(10, &model(0.0, 1.0, 0.0), &mut rng);
+
+        assert_eq!(yak.age, 10);
+        assert_eq!(products, Some(Products::default()));
+    }
+
+    #[test]
+    fn test_early_death() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut yak = default_yak();
+        let products = yak.step_days_stochastic(10, &model(0.0, 0.0, 1.0), &mut rng);
+
+        assert_eq!(yak.age, 0);
+        assert!(!yak.is_alive());
+        assert_eq!(products, Some(Products::default()));
+        assert_eq!(
+            yak.step_days_stochastic(10, &model(0.0, 0.0, 1.0), &mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn test_milk_noise_bounds() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut yak = default_yak();
+        let products = yak
+            .step_days_stochastic(1, &model(0.1, 0.0, 0.0), &mut rng)
+            .unwrap();
+
+        assert!((liters("45")..=liters("55")).contains(&products.milk()));
+    }
 }
@@ -1,79 +1,130 @@
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign};
 
-/// Milk production suffers from floating point errors. Do all math without floating point and
-/// adjust with this constant at the end to get the correct result.
-const MILK_FLOATING_POINT_ADJUSTMENT: u32 = 100;
-/// A yak can only be shaved after it is 100 days (1 year) old.
-pub const MIN_YAK_SHAVE_AGE: u32 = 100;
+use crate::{FarmConfig, Quantity, YakShopError};
 
-const BASE_MILK_PRODUCTION: u32 = 5_000;
-const MINIMUM_WOOL_SHAVING_PERIOD: f64 = 8.00;
-const WOOL_SHAVING_AGE_ADJUSTMENT: f64 = 0.01;
+/// The shaving period grows by 1 day for every `WOOL_SHAVING_AGE_DIVISOR` days of age.
+const WOOL_SHAVING_AGE_DIVISOR: u64 = 100;
 
-pub fn yak_milk_production(age: u32) -> u32 {
-    // SAFETY: yaks should die before they 1667 days old, however if technology advances and they
-    // are able to stay alive past that, we should ensure they don't start consuming milk instead.
-    BASE_MILK_PRODUCTION.saturating_sub(age * 3)
+/// The liters of milk a yak produces on a day at the given age.
+pub fn yak_milk_production(farm: &FarmConfig, age: u32) -> Quantity {
+    // SAFETY: yaks should die before they stop producing milk (1667 days old by default), however
+    // if technology advances and they are able to stay alive past that, we should ensure they don't
+    // start consuming milk instead.
+    let decline = u64::from(age).saturating_mul(farm.milk_production_decline.thousandths());
+    farm.base_milk_production
+        .saturating_sub(Quantity::from_thousandths(decline))
 }
 
-pub fn yak_can_produce_wool(age: u32, age_last_shaved: u32) -> bool {
-    if age < MIN_YAK_SHAVE_AGE {
-        return false;
-    }
+/// The first age (in days) at which a yak last shaved at `age_last_shaved` can be shaved again.
+pub fn yak_next_shave_day(farm: &FarmConfig, age_last_shaved: u32) -> u32 {
+    // A yak can be shaved once `age >= age_last_shaved + 8 + age * 0.01`. Rearranging gives
+    // `age * 99 >= (age_last_shaved + 8) * 100`, which can be solved for age without floats.
+    let period_start =
+        (u64::from(age_last_shaved) + u64::from(farm.shave_period)) * WOOL_SHAVING_AGE_DIVISOR;
+    let next_shave_day = period_start.div_ceil(WOOL_SHAVING_AGE_DIVISOR - 1);
+
+    // Yaks which can't be shaved again within `u32::MAX` days won't live that long anyway
+    u32::try_from(next_shave_day)
+        .unwrap_or(u32::MAX)
+        .max(farm.min_shave_age)
+}
 
-    let float_age = f64::from(age);
+/// The age at which a yak shaved at `age` can be shaved again, when its wool grows at `wool` times
+/// the usual rate.
+pub fn yak_seasonal_next_shave_day(farm: &FarmConfig, age: u32, wool: Quantity) -> u32 {
+    let period = u64::from(yak_next_shave_day(farm, age).saturating_sub(age));
 
-    // The next shave date is 8 + (0.01 * age years after the last shave)
-    let next_shave_date: f64 = f64::from(age_last_shaved)
-        + MINIMUM_WOOL_SHAVING_PERIOD
-        + (float_age * WOOL_SHAVING_AGE_ADJUSTMENT);
+    // SAFETY: A zero wool multiplier is rejected by `Seasons::new`
+    let period = (period * Quantity::SCALE).div_ceil(wool.thousandths().max(1));
 
-    float_age >= next_shave_date
+    u32::try_from(period).map_or(u32::MAX, |period| age.saturating_add(period))
 }
 
-#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Eq, PartialEq)]
 pub struct Products {
-    milk: u32,
+    milk: Quantity,
     wool: u32,
 }
 
+// The operators panic on overflow rather than wrapping around. Totals which are not bounded by the
+// lifetime of a single yak should use `checked_add` instead.
 impl Add<Products> for Products {
     type Output = Products;
 
     fn add(self, rhs: Products) -> Products {
-        Products {
-            milk: self.milk + rhs.milk,
-            wool: self.wool + rhs.wool,
-        }
+        self.checked_add(&rhs).expect("Products overflowed")
     }
 }
 
 impl AddAssign<Products> for Products {
     fn add_assign(&mut self, rhs: Products) {
-        *self = Products {
-            milk: self.milk + rhs.milk,
-            wool: self.wool + rhs.wool,
-        };
+        *self = self.checked_add(&rhs).expect("Products overflowed");
     }
 }
 
 impl Products {
     #[must_use]
-    pub fn new(milk: u32, wool: u32) -> Self {
+    pub fn new(milk: Quantity, wool: u32) -> Self {
         Products { milk, wool }
     }
 
-    pub fn add_milk(&mut self, milk: u32) {
+    /// Adds two sets of products together.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::Overflow` if either total does not fit.
+    pub fn checked_add(&self, rhs: &Products) -> Result<Products, YakShopError> {
+        Ok(Products {
+            milk: self
+                .milk
+                .checked_add(rhs.milk)
+                .ok_or_else(|| YakShopError::Overflow("milk".to_string()))?,
+            wool: self
+                .wool
+                .checked_add(rhs.wool)
+                .ok_or_else(|| YakShopError::Overflow("wool".to_string()))?,
+        })
+    }
+
+    /// Takes `rhs` away, or returns `None` if there is not enough of either product.
+    pub(crate) fn checked_sub(&self, rhs: &Products) -> Option<Products> {
+        Some(Products {
+            milk: self.milk.checked_sub(rhs.milk)?,
+            wool: self.wool.checked_sub(rhs.wool)?,
+        })
+    }
+
+    /// The part of an order for `milk` and `wool` which can be filled from these products. Each
+    /// product is either delivered in full or not at all.
+    pub(crate) fn fill(
+        &self,
+        milk: Option<Quantity>,
+        wool: Option<u32>,
+    ) -> (Option<Quantity>, Option<u32>) {
+        (
+            milk.filter(|milk| self.milk >= *milk),
+            wool.filter(|wool| self.wool >= *wool),
+        )
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the total milk overflows.
+    pub fn add_milk(&mut self, milk: Quantity) {
         self.milk += milk;
     }
 
+    /// # Panics
+    ///
+    /// Panics if the total wool overflows.
     pub fn add_wool(&mut self, wool: u32) {
-        self.wool += wool;
+        self.wool = self.wool.checked_add(wool).expect("Wool overflowed");
     }
 
     #[must_use]
-    pub fn milk(&self) -> f64 {
-        f64::from(self.milk) / f64::from(MILK_FLOATING_POINT_ADJUSTMENT)
+    pub fn milk(&self) -> Quantity {
+        self.milk
     }
 
     #[must_use]
@@ -86,14 +137,88 @@ impl Products {
 mod tests {
     use super::*;
 
+    fn liters(value: &str) -> Quantity {
+        value.parse().unwrap()
+    }
+
     #[test]
     fn test_yak_milk_production() {
-        assert_eq!(yak_milk_production(0), 5_000);
-        assert_eq!(yak_milk_production(100), 5_000 - 3 * 100);
+        let farm = FarmConfig::default();
+        assert_eq!(yak_milk_production(&farm, 0), liters("50"));
+        assert_eq!(yak_milk_production(&farm, 100), liters("47"));
         // Last day of milk production
-        assert_eq!(yak_milk_production(1666), 2);
+        assert_eq!(yak_milk_production(&farm, 1666), liters("0.02"));
         // Every day after that is 0
-        assert_eq!(yak_milk_production(1667), 0);
-        assert_eq!(yak_milk_production(1668), 0);
+        assert_eq!(yak_milk_production(&farm, 1667), Quantity::ZERO);
+        assert_eq!(yak_milk_production(&farm, 1668), Quantity::ZERO);
+    }
+
+    #[test]
+    fn test_yak_milk_production_configured() {
+        let farm = FarmConfig {
+            base_milk_production: liters("20"),
+            milk_production_decline: liters("0.01"),
+            ..FarmConfig::default()
+        };
+        assert_eq!(yak_milk_production(&farm, 0), liters("20"));
+        assert_eq!(yak_milk_production(&farm, 500), liters("15"));
+        assert_eq!(yak_milk_production(&farm, 2000), Quantity::ZERO);
+    }
+
+    #[test]
+    fn test_checked_add() {
+        let products = Products::new(liters("1.5"), 2);
+        assert_eq!(
+            products.checked_add(&products).unwrap(),
+            Products::new(liters("3"), 4)
+        );
+
+        let full_wool = Products::new(Quantity::ZERO, u32::MAX);
+        assert!(matches!(
+            full_wool.checked_add(&products),
+            Err(YakShopError::Overflow(_))
+        ));
+
+        let full_milk = Products::new(Quantity::from_thousandths(u64::MAX), 0);
+        assert!(matches!(
+            full_milk.checked_add(&products),
+            Err(YakShopError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_yak_next_shave_day() {
+        let farm = FarmConfig::default();
+        // Never shaved, so it can be shaved once it is old enough
+        assert_eq!(yak_next_shave_day(&farm, 0), farm.min_shave_age);
+        // 108 + 109 * 0.01 = 109.09 > 109, 108 + 110 * 0.01 = 109.1 <= 110
+        assert_eq!(yak_next_shave_day(&farm, 100), 110);
+        // 8 + 400 = 408, 408 + 413 * 0.01 = 412.13 <= 413
+        assert_eq!(yak_next_shave_day(&farm, 400), 413);
+        // Exactly on the day: 982 + 8 = 990, 990 + 1000 * 0.01 = 1000
+        assert_eq!(yak_next_shave_day(&farm, 982), 1000);
+    }
+
+    #[test]
+    fn test_yak_seasonal_next_shave_day() {
+        let farm = FarmConfig::default();
+        // The usual period from 400 is 13 days
+        assert_eq!(yak_seasonal_next_shave_day(&farm, 400, liters("1")), 413);
+        assert_eq!(yak_seasonal_next_shave_day(&farm, 400, liters("0.5")), 426);
+        // 13 / 1.5 = 8.67, rounded up
+        assert_eq!(yak_seasonal_next_shave_day(&farm, 400, liters("1.5")), 409);
+    }
+
+    #[test]
+    fn test_yak_next_shave_day_configured() {
+        let farm = FarmConfig {
+            min_shave_age: 365,
+            shave_period: 30,
+            ..FarmConfig::default()
+        };
+        assert_eq!(yak_next_shave_day(&farm, 0), 365);
+        // 400 + 30 = 430, 430 + 435 * 0.01 = 434.35 <= 435
+        assert_eq!(yak_next_shave_day(&farm, 400), 435);
+        assert_eq!(yak_next_shave_day(&farm, u32::MAX), u32::MAX);
     }
 }
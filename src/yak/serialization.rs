@@ -1,29 +1,59 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Serialize};
 
-use super::DAYS_IN_YAK_YEAR;
+use super::Sex;
+use crate::{FarmConfig, Quantity, YakShopError};
 
-pub fn yak_float_years_to_days<'de, D>(deserializer: D) -> Result<u32, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let mut years = f64::deserialize(deserializer)?;
+/// A yak as described in a herd file, with its ages in yak years.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct YakRecord {
+    pub name: String,
+    pub age: Quantity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sex: Option<Sex>,
+    /// Only present in herds exported from a running shop.
+    #[serde(default, skip_serializing_if = "Quantity::is_zero")]
+    pub age_last_shaved: Quantity,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deceased: bool,
+}
 
-    years *= DAYS_IN_YAK_YEAR;
+/// Converts an age in yak years to days. Parsing the years as a quantity keeps ages such as 4.13
+/// years exact.
+pub fn yak_years_to_days(farm: &FarmConfig, years: Quantity) -> Result<u32, YakShopError> {
+    // Partial days are truncated
+    let days = u128::from(years.thousandths()) * u128::from(farm.days_in_yak_year)
+        / u128::from(Quantity::SCALE);
 
-    match years {
-        years if years.is_nan() => Err(serde::de::Error::custom("Yak age is NaN")),
-        years if years.is_infinite() => Err(serde::de::Error::custom("Yak age is infinite")),
-        years if years.is_sign_negative() => {
-            Err(serde::de::Error::custom("Yak age cannot be negative"))
-        }
-        years if (years > f64::from(u32::MAX)) => {
-            Err(serde::de::Error::custom("Yak age is too large"))
-        }
-        _ => {
-            // SAFETY: The following  allows have been checked above
-            #[allow(clippy::cast_possible_truncation)]
-            #[allow(clippy::cast_sign_loss)]
-            Ok(years as u32)
+    u32::try_from(days)
+        .map_err(|_| YakShopError::ConfigFileParseError("Yak age is too large".to_string()))
+}
+
+/// Converts an age in days to yak years. Rounded up to the next thousandth, so it converts back
+/// to the same number of days for yak years of up to 1000 days.
+pub fn yak_days_to_years(farm: &FarmConfig, days: u32) -> Quantity {
+    let thousandths =
+        (u64::from(days) * Quantity::SCALE).div_ceil(u64::from(farm.days_in_yak_year));
+    Quantity::from_thousandths(thousandths)
+}
+
+/// Converts an age in days to yak years as a float, for display. Unlike `yak_days_to_years`, it
+/// doesn't always convert back to the same number of days.
+pub fn yak_days_to_float_years(farm: &FarmConfig, days: u32) -> f64 {
+    f64::from(days) / f64::from(farm.days_in_yak_year)
+}
+
+/// Escapes the characters which are not allowed in XML attribute values.
+pub fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
         }
     }
+    escaped
 }
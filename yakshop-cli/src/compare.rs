@@ -0,0 +1,228 @@
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use yakshop::{HerdFormat, Products, Quantity, ShopConfig};
+
+use crate::load_shop;
+
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// The herd file to use as the baseline, or `-` to read it from stdin.
+    pub herd_a: PathBuf,
+
+    /// The herd file to compare against the baseline, or `-` to read it from stdin.
+    pub herd_b: PathBuf,
+
+    /// The number of days to run both simulations for (max: `u32::MAX`)
+    #[arg(long)]
+    pub days: u32,
+
+    /// The format of both herd files (default: detected from the file extension or content)
+    #[arg(long)]
+    pub input_format: Option<HerdFormat>,
+
+    /// The shop configuration file for both herds (default: `yakshop.toml` in the working
+    /// directory, if present)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// The output format of the comparison
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct StockSummary {
+    milk: Quantity,
+    wool: u32,
+}
+
+impl From<&Products> for StockSummary {
+    fn from(products: &Products) -> Self {
+        StockSummary {
+            milk: products.milk(),
+            wool: products.wool(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct YakSummary {
+    name: String,
+    age: f64,
+    alive: bool,
+}
+
+#[derive(Serialize)]
+struct ShopSummary {
+    herd_file: PathBuf,
+    stock: StockSummary,
+    herd_size: usize,
+    surviving_yaks: usize,
+    herd: Vec<YakSummary>,
+    /// The products produced on each day, starting with day 0.
+    daily_production: Vec<StockSummary>,
+}
+
+impl ShopSummary {
+    /// Runs the simulation for `herd_file` one day at a time, recording each day's production.
+    fn simulate(
+        herd_file: &Path,
+        format: Option<HerdFormat>,
+        config: &ShopConfig,
+        days: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut shop = load_shop(herd_file, format, config)?;
+
+        let daily_production = (0..days)
+            .map(|_| {
+                shop.step_days(1)
+                    .map(|products| StockSummary::from(&products))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let herd: Vec<YakSummary> = shop
+            .yaks()
+            .iter()
+            .map(|yak| YakSummary {
+                name: yak.name().to_string(),
+                age: yak.year_age(),
+                alive: yak.is_alive(),
+            })
+            .collect();
+
+        Ok(ShopSummary {
+            herd_file: herd_file.to_path_buf(),
+            stock: StockSummary::from(&shop.produced_products),
+            herd_size: herd.len(),
+            surviving_yaks: herd.iter().filter(|yak| yak.alive).count(),
+            herd,
+            daily_production,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Difference {
+    milk: f64,
+    wool: i64,
+    surviving_yaks: i64,
+}
+
+#[derive(Serialize)]
+struct Comparison {
+    days: u32,
+    a: ShopSummary,
+    b: ShopSummary,
+    /// The difference of `b` relative to `a`.
+    difference: Difference,
+}
+
+impl Comparison {
+    fn new(a: ShopSummary, b: ShopSummary, days: u32) -> Self {
+        let difference = Difference {
+            milk: quantity_difference(a.stock.milk, b.stock.milk),
+            wool: i64::from(b.stock.wool) - i64::from(a.stock.wool),
+            surviving_yaks: count_difference(a.surviving_yaks, b.surviving_yaks),
+        };
+
+        Comparison {
+            days,
+            a,
+            b,
+            difference,
+        }
+    }
+}
+
+fn quantity_difference(a: Quantity, b: Quantity) -> f64 {
+    if b >= a {
+        (b - a).to_f64()
+    } else {
+        -(a - b).to_f64()
+    }
+}
+
+fn count_difference(a: usize, b: usize) -> i64 {
+    // SAFETY: herd sizes are never anywhere near i64::MAX
+    #[allow(clippy::cast_possible_wrap)]
+    let difference = b as i64 - a as i64;
+    difference
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (a, b) = (&self.a, &self.b);
+
+        writeln!(f, "After {} days:", self.days)?;
+        writeln!(f, "{:<16}{:>14}{:>14}{:>14}", "", "A", "B", "B - A")?;
+        writeln!(
+            f,
+            "{:<16}{:>14}{:>14}{:>+14.3}",
+            "Milk (liters)", a.stock.milk, b.stock.milk, self.difference.milk
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:>14}{:>14}{:>+14}",
+            "Wool (skins)", a.stock.wool, b.stock.wool, self.difference.wool
+        )?;
+        writeln!(
+            f,
+            "{:<16}{:>14}{:>14}{:>+14}",
+            "Surviving yaks",
+            format!("{}/{}", a.surviving_yaks, a.herd_size),
+            format!("{}/{}", b.surviving_yaks, b.herd_size),
+            self.difference.surviving_yaks
+        )?;
+        writeln!(f, "  A: {}", a.herd_file.to_string_lossy())?;
+        writeln!(f, "  B: {}", b.herd_file.to_string_lossy())?;
+
+        write!(f, "Daily production:")?;
+        write!(
+            f,
+            "\n{:<8}{:>12}{:>8}{:>12}{:>8}",
+            "Day", "A milk", "A wool", "B milk", "B wool"
+        )?;
+        for (day, (a, b)) in a
+            .daily_production
+            .iter()
+            .zip(&b.daily_production)
+            .enumerate()
+        {
+            write!(
+                f,
+                "\n{:<8}{:>12}{:>8}{:>12}{:>8}",
+                day, a.milk, a.wool, b.milk, b.wool
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the simulation for both herd files and prints the comparison in the requested format.
+pub fn run(args: &CompareArgs) -> Result<(), Box<dyn Error>> {
+    let config = ShopConfig::load(args.config.as_deref())?;
+
+    let comparison = Comparison::new(
+        ShopSummary::simulate(&args.herd_a, args.input_format, &config, args.days)?,
+        ShopSummary::simulate(&args.herd_b, args.input_format, &config, args.days)?,
+        args.days,
+    );
+
+    match args.format {
+        OutputFormat::Text => println!("{comparison}"),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&comparison)?),
+    }
+
+    Ok(())
+}
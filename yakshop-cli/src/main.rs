@@ -11,35 +11,157 @@
 #![warn(unused_qualifications)]
 #![warn(variant_size_difference)]
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use yakshop::Shop;
+use yakshop::{HerdFormat, MonteCarlo, Shop, ShopConfig, ShopDay, StochasticModel, YakShopError};
+
+mod compare;
+mod orders;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
-    /// The location to the herd.xml file to use as a data source.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The location to the herd file (xml, json, yaml or toml) to use as a data source, or `-` to
+    /// read it from stdin.
     #[arg(required = true)]
-    pub herd: PathBuf,
+    pub herd: Option<PathBuf>,
 
-    /// The number of days to run the simulation for (max: `u32::MAX`)
+    /// The number of days to run the simulation for (max: `u32::MAX`), or the date to run it
+    /// until (`YYYY-MM-DD`) when the configuration has a calendar start date.
     #[arg(required = true)]
-    pub days: u32,
+    pub days: Option<ShopDay>,
+
+    /// The format of the herd file (default: detected from the file extension or content)
+    #[arg(long)]
+    pub input_format: Option<HerdFormat>,
+
+    /// The shop configuration file (default: `yakshop.toml` in the working directory, if present)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Write the herd at the end of the simulation to this herd.xml file
+    #[arg(long, conflicts_with = "simulations")]
+    pub export: Option<PathBuf>,
+
+    /// Leave yaks which have died out of the exported herd
+    #[arg(long, requires = "export")]
+    pub remove_dead: bool,
+
+    /// Also print the profit and loss, with the feed and care costs of the herd
+    #[arg(long, conflicts_with = "simulations")]
+    pub pnl: bool,
+
+    /// A JSON file of orders to deliver on their days, for the revenue in the profit and loss.
+    /// Orders after the last day are ignored.
+    #[arg(long, requires = "pnl")]
+    pub orders: Option<PathBuf>,
+
+    /// Run this many stochastic simulations (at most 10000) and report the stock distribution instead
+    #[arg(long)]
+    pub simulations: Option<u32>,
+
+    /// The seed for the stochastic simulations (default: random)
+    #[arg(long, requires = "simulations")]
+    pub seed: Option<u64>,
+
+    /// The maximum relative deviation of daily milk yield in stochastic simulations
+    #[arg(long, default_value = "0.1", requires = "simulations")]
+    pub milk_noise: f64,
+
+    /// The daily chance of a yak being ill (no yield) in stochastic simulations
+    #[arg(long, default_value = "0.01", requires = "simulations")]
+    pub illness_rate: f64,
+
+    /// The daily chance of a yak dying early in stochastic simulations
+    #[arg(long, default_value = "0.0005", requires = "simulations")]
+    pub mortality_rate: f64,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the simulation for two herd files and compare the results.
+    Compare(compare::CompareArgs),
+}
+
+impl Args {
+    fn stochastic_model(&self) -> StochasticModel {
+        StochasticModel {
+            milk_noise: self.milk_noise,
+            illness_rate: self.illness_rate,
+            mortality_rate: self.mortality_rate,
+        }
+    }
+}
+
+/// Loads the herd from `herd`, or from stdin if the path is `-`.
+fn load_shop(
+    herd: &Path,
+    format: Option<HerdFormat>,
+    config: &ShopConfig,
+) -> Result<Shop, YakShopError> {
+    if herd == Path::new("-") {
+        return Shop::from_reader(std::io::stdin().lock(), format, config);
+    }
+
+    Shop::from_path(herd, format, config)
 }
 
 /// Main entry point for the CLI application. This application accepts a path to a herd.xml file and
 /// a number of days to run the simulation for. It will then output the state of the shop at the end
 /// of the simulation.
+///
+/// With `--pnl`, it also outputs the profit and loss of the shop, including the revenue of the
+/// orders given with `--orders`.
+///
+/// When `--simulations` is provided, it instead runs that many stochastic simulations and outputs
+/// the distribution of the stock at the end of them.
+///
+/// The `compare` subcommand runs the simulation for two herd files side by side.
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let mut shop = Shop::try_from(&args.herd)?;
+    if let Some(Command::Compare(compare_args)) = &args.command {
+        return compare::run(compare_args);
+    }
+
+    // SAFETY: Both arguments are required by clap when no subcommand is given.
+    let herd = args.herd.as_ref().expect("herd is a required argument");
+    let days = args.days.expect("days is a required argument");
 
-    shop.step_days(args.days);
+    let config = ShopConfig::load(args.config.as_deref())?;
+    let mut shop = load_shop(herd, args.input_format, &config)?;
+    let days = shop.day(days)?;
+
+    if let Some(runs) = args.simulations {
+        let seed = args.seed.unwrap_or_else(rand::random);
+        let simulation = MonteCarlo::new(args.stochastic_model(), runs, seed)?;
+
+        println!("{}", simulation.run(&shop, days)?);
+
+        return Ok(());
+    }
+
+    let orders = match &args.orders {
+        Some(path) => orders::load(path)?,
+        None => Vec::new(),
+    };
+    let delivered = orders::deliver(&mut shop, orders, days)?;
 
     println!("{shop}");
 
+    if args.pnl {
+        println!("{}", shop.profit_and_loss(&delivered)?);
+    }
+
+    if let Some(export) = &args.export {
+        std::fs::write(export, shop.to_xml(!args.remove_dead))?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+
+use yakshop::{Order, Quantity, Shop, YakShopError};
+
+/// An order from an orders file, as placed with the web shop on the given day, e.g.:
+///
+/// ```json
+/// [{"day": 13, "customer": "Medvedev", "order": {"milk": 1100, "skins": 3}}]
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct PlannedOrder {
+    day: u32,
+    customer: String,
+    order: OrderItems,
+}
+
+#[derive(Deserialize, Debug)]
+struct OrderItems {
+    milk: Option<Quantity>,
+    skins: Option<u32>,
+}
+
+/// Reads the orders from a JSON file.
+pub fn load(path: &Path) -> Result<Vec<PlannedOrder>, Box<dyn Error>> {
+    let orders = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&orders)?)
+}
+
+/// Steps the shop forward by `days`, delivering the orders placed up to then on their days. Orders
+/// are filled the same way as the web shop does, from the stock on the day.
+pub fn deliver(
+    shop: &mut Shop,
+    mut orders: Vec<PlannedOrder>,
+    days: u32,
+) -> Result<Vec<Order>, YakShopError> {
+    orders.retain(|order| order.day <= days);
+    orders.sort_by_key(|order| order.day);
+
+    let mut delivered = Vec::new();
+    for order in orders {
+        shop.step_days(order.day - shop.elapsed_days)?;
+
+        let consumed = shop.consume_products(order.order.milk, order.order.skins);
+        if consumed != (None, None) {
+            delivered.push(shop.order(&order.customer, consumed));
+        }
+    }
+
+    shop.step_days(days - shop.elapsed_days)?;
+
+    Ok(delivered)
+}
@@ -0,0 +1,412 @@
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+use yakshop::{Event, Products};
+
+use crate::store::Receipt;
+
+pub use rusqlite::Error;
+
+/// The changes to the schema, in order. The number of migrations applied is kept in the
+/// `user_version` of the database, so released migrations must never change; add a new one instead.
+const MIGRATIONS: &[&str] = &[
+    r"
+    -- Every version of the herd of a shop, as a JSON herd file
+    CREATE TABLE herds (
+        id INTEGER PRIMARY KEY,
+        shop TEXT NOT NULL,
+        herd TEXT NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX herds_shop ON herds (shop, id);
+
+    -- The orders delivered, with the milk in thousandths of a liter
+    CREATE TABLE orders (
+        id INTEGER PRIMARY KEY,
+        shop TEXT NOT NULL,
+        customer TEXT NOT NULL,
+        day INTEGER NOT NULL,
+        milk INTEGER NOT NULL,
+        wool INTEGER NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX orders_shop ON orders (shop, id);
+
+    -- The stock reported for a day, with the milk in thousandths of a liter
+    CREATE TABLE stock_snapshots (
+        id INTEGER PRIMARY KEY,
+        shop TEXT NOT NULL,
+        day INTEGER NOT NULL,
+        milk INTEGER NOT NULL,
+        wool INTEGER NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+
+    -- What happened to the shops, with the details as JSON
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY,
+        shop TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        detail TEXT NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+",
+    r"
+    -- The decisions in the journal of a shop, as JSON events, which replace the orders. The
+    -- orders weren't taken from the stock, so they are imported to be capped to it. They could
+    -- be placed for any day, so they are imported and numbered in the order of their days
+    CREATE TABLE journal (
+        id INTEGER PRIMARY KEY,
+        shop TEXT NOT NULL,
+        day INTEGER NOT NULL,
+        event TEXT NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+    CREATE INDEX journal_shop ON journal (shop, id);
+
+    INSERT INTO journal (shop, day, event, recorded_at)
+    SELECT shop, day, json_object(
+        'event', 'order_imported',
+        'id', row_number() OVER (PARTITION BY shop ORDER BY day, id),
+        'order', json_object(
+            'customer', customer,
+            'day', day,
+            'delivered', json_object('milk', milk / 1000.0, 'wool', wool)
+        )
+    ), recorded_at
+    FROM orders
+    ORDER BY day, id;
+
+    DROP TABLE orders;
+",
+    r"
+    -- The receipts of the orders placed with an idempotency key, as JSON
+    CREATE TABLE order_receipts (
+        shop TEXT NOT NULL,
+        key TEXT NOT NULL,
+        receipt TEXT NOT NULL,
+        recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (shop, key)
+    );
+",
+];
+
+/// A `SQLite` database keeping the herds, orders, stock snapshots and events of the shops, so they
+/// survive a restart of the server.
+pub struct Database {
+    connection: Mutex<Connection>,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if needed, and brings its schema up to date.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Database::new(Connection::open(path)?)
+    }
+
+    fn new(mut connection: Connection) -> Result<Self, Error> {
+        migrate(&mut connection)?;
+
+        Ok(Database {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().expect("database lock poisoned")
+    }
+
+    /// Keeps the herd of a shop, unless it is the same as the last herd kept. Returns whether the
+    /// herd changed.
+    pub fn save_herd(&self, shop: &str, herd: &str) -> Result<bool, Error> {
+        let connection = self.connection();
+
+        let last: Option<String> = connection
+            .query_row(
+                "SELECT herd FROM herds WHERE shop = ?1 ORDER BY id DESC LIMIT 1",
+                params![shop],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if last.as_deref() == Some(herd) {
+            return Ok(false);
+        }
+
+        connection.execute(
+            "INSERT INTO herds (shop, herd) VALUES (?1, ?2)",
+            params![shop, herd],
+        )?;
+        Ok(true)
+    }
+
+    /// The events kept for a shop, in the order they happened.
+    pub fn journal(&self, shop: &str) -> Result<Vec<Event>, Error> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT event FROM journal WHERE shop = ?1 ORDER BY id")?;
+
+        let events = statement.query_map(params![shop], |row| {
+            let event: String = row.get(0)?;
+            serde_json::from_str(&event)
+                .map_err(|err| Error::FromSqlConversionFailure(0, Type::Text, Box::new(err)))
+        })?;
+        events.collect()
+    }
+
+    /// The receipts of the orders a shop placed with an idempotency key, by key.
+    pub fn receipts(&self, shop: &str) -> Result<Vec<(String, Receipt)>, Error> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT key, receipt FROM order_receipts WHERE shop = ?1")?;
+
+        let receipts = statement.query_map(params![shop], |row| {
+            let receipt: String = row.get(1)?;
+            let receipt = serde_json::from_str(&receipt)
+                .map_err(|err| Error::FromSqlConversionFailure(1, Type::Text, Box::new(err)))?;
+            Ok((row.get(0)?, receipt))
+        })?;
+        receipts.collect()
+    }
+
+    /// Adds events to the journal of a shop, along with the receipt of the order they deliver
+    /// under its idempotency key, all or none of them.
+    pub fn save_events(
+        &self,
+        shop: &str,
+        events: &[&Event],
+        receipt: Option<(&str, &Receipt)>,
+    ) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+
+        for event in events {
+            transaction.execute(
+                "INSERT INTO journal (shop, day, event) VALUES (?1, ?2, ?3)",
+                params![shop, event.day(), to_json(event)?],
+            )?;
+        }
+        if let Some((key, receipt)) = receipt {
+            transaction.execute(
+                "INSERT INTO order_receipts (shop, key, receipt) VALUES (?1, ?2, ?3)",
+                params![shop, key, to_json(receipt)?],
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    pub fn save_stock(&self, shop: &str, day: u32, stock: &Products) -> Result<(), Error> {
+        self.connection().execute(
+            "INSERT INTO stock_snapshots (shop, day, milk, wool) VALUES (?1, ?2, ?3, ?4)",
+            params![shop, day, stock.milk().thousandths(), stock.wool()],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_event(&self, shop: &str, kind: &str, detail: &str) -> Result<(), Error> {
+        self.connection().execute(
+            "INSERT INTO events (shop, kind, detail) VALUES (?1, ?2, ?3)",
+            params![shop, kind, detail],
+        )?;
+        Ok(())
+    }
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, Error> {
+    serde_json::to_string(value).map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))
+}
+
+/// Applies the migrations the database has not seen yet, each in a transaction of its own.
+fn migrate(connection: &mut Connection) -> Result<(), Error> {
+    let applied: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", version + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yakshop::{Journal, Order, Shop, ShopConfig};
+
+    fn database() -> Database {
+        Database::new(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_migrate() {
+        let database = database();
+        let mut connection = database.connection();
+
+        let version: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        // Migrating again leaves the database as it is
+        migrate(&mut connection).unwrap();
+    }
+
+    fn order_event(id: u32) -> Event {
+        Event::OrderDelivered {
+            id,
+            order: Order {
+                customer: "Medvedev".to_string(),
+                day: 13,
+                delivered: Products::new("1100.125".parse().unwrap(), 3),
+            },
+        }
+    }
+
+    #[test]
+    fn test_journal() {
+        let database = database();
+        let event = order_event(1);
+
+        database.save_events("valley", &[&event], None).unwrap();
+        database.save_events("highlands", &[&event], None).unwrap();
+
+        assert_eq!(database.journal("valley").unwrap(), [event]);
+        assert_eq!(database.journal("lowlands").unwrap(), []);
+    }
+
+    #[test]
+    fn test_receipts() {
+        let database = database();
+        let receipt: Receipt = serde_json::from_str(
+            r#"{"day":13,"customer":"Medvedev","milk":1100.0,"skins":null,
+                "delivery":{"id":1,"milk":1100.0,"wool":null}}"#,
+        )
+        .unwrap();
+
+        database
+            .save_events("valley", &[&order_event(1)], Some(("first", &receipt)))
+            .unwrap();
+        assert_eq!(
+            database.receipts("valley").unwrap(),
+            [("first".to_string(), receipt.clone())]
+        );
+
+        // A key is only used once, so the events aren't kept either
+        assert!(database
+            .save_events("valley", &[&order_event(2)], Some(("first", &receipt)))
+            .is_err());
+        assert_eq!(database.journal("valley").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_orders() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(MIGRATIONS[0])
+            .and_then(|()| connection.pragma_update(None, "user_version", 1))
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO orders (shop, customer, day, milk, wool) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params!["valley", "Medvedev", 13, 1_100_125, 3],
+            )
+            .unwrap();
+
+        let database = Database::new(connection).unwrap();
+        let Event::OrderDelivered { id, order } = order_event(1) else {
+            unreachable!()
+        };
+        assert_eq!(
+            database.journal("valley").unwrap(),
+            [Event::OrderImported { id, order }]
+        );
+    }
+
+    /// Orders used to be delivered without taking them from the stock, so more could be sold
+    /// than produced. The shop still replays its journal, selling only what was produced.
+    #[test]
+    fn test_migrate_oversold_orders() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(MIGRATIONS[0])
+            .and_then(|()| connection.pragma_update(None, "user_version", 1))
+            .unwrap();
+        for customer in ["Medvedev", "Putin"] {
+            connection
+                .execute(
+                    "INSERT INTO orders (shop, customer, day, milk, wool) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params!["valley", customer, 13, 1_100_000, 3],
+                )
+                .unwrap();
+        }
+
+        let database = Database::new(connection).unwrap();
+        let herd = Path::new("../tests/fixtures/valid_multi.xml");
+        let shop = Shop::from_path(herd, None, &ShopConfig::default()).unwrap();
+        let journal = Journal::from_events(shop, &database.journal("valley").unwrap()).unwrap();
+
+        let delivered: Vec<_> = journal
+            .orders(13)
+            .into_iter()
+            .map(|(id, order)| (id, order.delivered))
+            .collect();
+        assert_eq!(
+            delivered,
+            [
+                (1, Products::new("1100".parse().unwrap(), 3)),
+                (2, Products::new("4.48".parse().unwrap(), 0)),
+            ]
+        );
+        assert_eq!(journal.stock(13).unwrap(), Some(Products::default()));
+    }
+
+    /// Orders could be placed for any day, so a later order can be for an earlier day. The
+    /// journal keeps its events in the order of their days, so they are imported in that order.
+    #[test]
+    fn test_migrate_orders_out_of_day_order() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(MIGRATIONS[0])
+            .and_then(|()| connection.pragma_update(None, "user_version", 1))
+            .unwrap();
+        for (customer, day) in [("Medvedev", 40), ("Putin", 13)] {
+            connection
+                .execute(
+                    "INSERT INTO orders (shop, customer, day, milk, wool) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params!["valley", customer, day, 1_000_000, 1],
+                )
+                .unwrap();
+        }
+
+        let database = Database::new(connection).unwrap();
+        let herd = Path::new("../tests/fixtures/valid_multi.xml");
+        let shop = Shop::from_path(herd, None, &ShopConfig::default()).unwrap();
+        let journal = Journal::from_events(shop, &database.journal("valley").unwrap()).unwrap();
+
+        let customers = |day| -> Vec<_> {
+            journal
+                .orders(day)
+                .into_iter()
+                .map(|(id, order)| (id, order.customer))
+                .collect()
+        };
+        assert_eq!(customers(13), [(1, "Putin".to_string())]);
+        assert_eq!(
+            customers(40),
+            [(1, "Putin".to_string()), (2, "Medvedev".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_save_herd() {
+        let database = database();
+
+        assert!(database.save_herd("valley", "a").unwrap());
+        assert!(!database.save_herd("valley", "a").unwrap());
+        assert!(database.save_herd("valley", "b").unwrap());
+        assert!(database.save_herd("highlands", "b").unwrap());
+    }
+}
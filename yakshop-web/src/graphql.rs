@@ -0,0 +1,541 @@
+//! A GraphQL schema of the shops, served at `/graphql` with the `graphql` feature. It answers
+//! from the same journals as the other routes, and orders placed with `placeOrder` are fulfilled
+//! the same way as `POST /yakshop/order/{day}`.
+//!
+//! GraphQL has no unsigned integers, so days, ids and counts are `Int`s, which can't go past
+//! `i32::MAX`. Dates are ISO date strings, and amounts exact decimal strings, though orders take
+//! plain numbers too.
+
+use juniper::parser::ScalarToken;
+use juniper::{
+    graphql_value, DefaultScalarValue, FieldError, FieldResult, GraphQLEnum, GraphQLInputObject,
+    GraphQLObject, InputValue, ParseScalarResult, ParseScalarValue, RootNode, ScalarValue, Value,
+};
+use std::sync::Arc;
+use warp::Filter;
+
+use yakshop::{Calendar, NaiveDate, Order, Products, Sex, ShopDay, Yak, YakShopError};
+
+use crate::store::{Ledger, Store};
+use crate::{ApiError, Liters, OrderItems, OrderRequest};
+
+/// The schema served at `/graphql`.
+pub(crate) type YakShopSchema = RootNode<'static, Query, Mutation>;
+
+/// The shops the resolvers answer from.
+pub(crate) struct Context {
+    store: Arc<Store>,
+}
+
+impl juniper::Context for Context {}
+
+/// The schema of the shops.
+pub(crate) fn schema() -> YakShopSchema {
+    RootNode::new(Query, Mutation)
+}
+
+/// The `/graphql` route, answering queries and mutations posted as JSON.
+pub(crate) fn routes(
+    store: &Arc<Store>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let schema = Arc::new(schema());
+    let store = store.clone();
+
+    warp::path!("graphql")
+        .and(warp::post())
+        .and(crate::json_body())
+        .and_then(move |request: juniper::http::GraphQLRequest| {
+            let (schema, store) = (schema.clone(), store.clone());
+            async move {
+                // Placing an order can step the shop through many days, so keep it off the
+                // threads serving the other requests
+                let response = crate::blocking(move || {
+                    let context = Context { store };
+                    Ok::<_, ApiError>(warp::reply::json(&request.execute(&schema, &context)))
+                })
+                .await;
+
+                let result = match response {
+                    Ok(response) => warp::reply::with_status(response, warp::http::StatusCode::OK),
+                    Err(err) => err.reply(),
+                };
+                Ok::<_, std::convert::Infallible>(result)
+            }
+        })
+}
+
+impl From<ApiError> for FieldError {
+    fn from(err: ApiError) -> Self {
+        let code = err.code;
+        let extensions = match err.field {
+            Some(field) => graphql_value!({ "code": code, "field": field }),
+            None => graphql_value!({ "code": code }),
+        };
+
+        FieldError::new(err.message, extensions)
+    }
+}
+
+/// Converts a library error to a GraphQL error with the `code` of the REST routes.
+fn error(err: &YakShopError) -> FieldError {
+    ApiError::from(err).into()
+}
+
+/// A day, id or count as a GraphQL `Int`, capped at `i32::MAX`.
+fn int(value: u32) -> i32 {
+    i32::try_from(value).unwrap_or(i32::MAX)
+}
+
+/// A date as an ISO date string.
+fn date(date: Option<NaiveDate>) -> Option<String> {
+    date.map(|date| date.to_string())
+}
+
+/// The ledger of the shop named `name`, or of the shop served without a name.
+fn ledger(context: &Context, name: Option<String>) -> FieldResult<(String, Ledger)> {
+    let shops = context.store.shops();
+    let name = name.or_else(|| shops.default_shop.clone()).ok_or_else(|| {
+        error(&YakShopError::InvalidParameter(
+            "A shop name is needed".into(),
+        ))
+    })?;
+
+    match shops.get(&name) {
+        Some(ledger) => Ok((name, ledger)),
+        None => Err(ApiError::new(
+            warp::http::StatusCode::NOT_FOUND,
+            "not_found",
+            format!("There is no shop {name}"),
+        )
+        .into()),
+    }
+}
+
+/// Parses a day number, or an ISO date in calendar mode.
+fn shop_day(day: &str) -> FieldResult<ShopDay> {
+    day.parse().map_err(|err| error(&err))
+}
+
+pub(crate) struct Query;
+
+#[juniper::object(Context = Context)]
+impl Query {
+    /// The names of the shops served
+    fn shops(context: &Context) -> Vec<String> {
+        context.store.shops().ledgers().keys().cloned().collect()
+    }
+
+    /// A shop as it is on a day. The shop can be left out when a single herd file is served.
+    #[graphql(arguments(day(description = "The day number, or an ISO date in calendar mode")))]
+    fn shop(context: &Context, name: Option<String>, day: String) -> FieldResult<ShopObject> {
+        let (name, ledger) = ledger(context, name)?;
+        let shop = crate::replay(&ledger, shop_day(&day)?).map_err(|err| error(&err))?;
+
+        Ok(ShopObject { name, ledger, shop })
+    }
+}
+
+pub(crate) struct Mutation;
+
+#[juniper::object(Context = Context)]
+impl Mutation {
+    /// Places an order on a day. Each product is delivered in full or not at all, and taken from
+    /// the stock. Orders can't be placed on a day the shop has passed.
+    #[graphql(arguments(
+        day(description = "The day number, or an ISO date in calendar mode"),
+        idempotency_key(
+            description = "A unique key for the order, so retries with it aren't delivered twice"
+        ),
+    ))]
+    fn place_order(
+        context: &Context,
+        shop: Option<String>,
+        day: String,
+        customer: String,
+        order: OrderInput,
+        idempotency_key: Option<String>,
+    ) -> FieldResult<DeliveryObject> {
+        let (_, ledger) = ledger(context, shop)?;
+        let request = OrderRequest {
+            customer,
+            order: OrderItems {
+                milk: order.milk.map(|Amount(milk)| milk),
+                skins: order.skins,
+            },
+        };
+
+        let (delivery, status) = crate::place_order(
+            shop_day(&day)?,
+            idempotency_key.as_deref(),
+            &request,
+            &ledger,
+        )?;
+
+        Ok(DeliveryObject {
+            // Only orders which delivered something get here, and those have an id
+            id: int(delivery.id.unwrap_or_default()),
+            milk: delivery.milk.map(|milk| milk.to_string()),
+            skins: delivery.wool.map(int),
+            complete: status == warp::http::StatusCode::CREATED,
+        })
+    }
+}
+
+/// The products ordered
+#[derive(GraphQLInputObject)]
+#[graphql(name = "OrderInput", scalar = "DefaultScalarValue")]
+pub(crate) struct OrderInput {
+    /// In liters
+    milk: Option<Amount>,
+    skins: Option<f64>,
+}
+
+/// An amount ordered, as an exact decimal string or a number.
+pub(crate) struct Amount(Liters);
+
+juniper::graphql_scalar!(Amount {
+    description: "An amount, as an exact decimal string such as \"1100.500\", or a number"
+
+    resolve(&self) -> Value {
+        match &self.0 {
+            Liters::Number(amount) => Value::scalar(*amount),
+            Liters::Exact(amount) => Value::scalar(amount.clone()),
+        }
+    }
+
+    from_input_value(value: &InputValue) -> Option<Amount> {
+        let InputValue::Scalar(value) = value else {
+            return None;
+        };
+        value
+            .as_string()
+            .map(Liters::Exact)
+            .or_else(|| value.as_float().map(Liters::Number))
+            .map(Amount)
+    }
+
+    from_str<'a>(value: ScalarToken<'a>) -> ParseScalarResult<'a> {
+        match value {
+            ScalarToken::String(_) => <String as ParseScalarValue>::from_str(value),
+            _ => <f64 as ParseScalarValue>::from_str(value),
+        }
+    }
+});
+
+/// The products delivered for an order
+#[derive(GraphQLObject)]
+#[graphql(name = "Delivery")]
+pub(crate) struct DeliveryObject {
+    id: i32,
+    /// In liters, as an exact decimal string, or `null` if no milk was delivered
+    milk: Option<String>,
+    /// `null` if no skins were delivered
+    skins: Option<i32>,
+    /// Whether the whole order was delivered
+    complete: bool,
+}
+
+/// A shop on a day
+pub(crate) struct ShopObject {
+    name: String,
+    ledger: Ledger,
+    /// The shop replayed up to the day
+    shop: yakshop::Shop,
+}
+
+#[juniper::object(Context = Context, name = "Shop")]
+impl ShopObject {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn day(&self) -> i32 {
+        int(self.shop.elapsed_days)
+    }
+
+    /// The ISO date of the day, or `null` without a calendar start date
+    fn date(&self) -> Option<String> {
+        date(self.shop.date())
+    }
+
+    /// The milk and wool produced up to the day, less the orders delivered by then
+    fn stock(&self) -> FieldResult<ProductsObject> {
+        let (_, stock, _) = crate::stock(&self.ledger, ShopDay::Number(self.shop.elapsed_days))
+            .map_err(|err| error(&err))?;
+
+        Ok(ProductsObject(stock))
+    }
+
+    /// The yaks, including the dead ones
+    fn herd(&self) -> Vec<YakObject> {
+        let calendar = self.shop.config().calendar;
+        self.shop
+            .yaks()
+            .iter()
+            .map(|yak| YakObject {
+                yak: yak.clone(),
+                calendar,
+            })
+            .collect()
+    }
+
+    /// The yak named `name`, or `null` if there is none
+    fn yak(&self, name: String) -> Option<YakObject> {
+        let calendar = self.shop.config().calendar;
+        self.shop.yak(&name).map(|yak| YakObject {
+            yak: yak.clone(),
+            calendar,
+        })
+    }
+
+    /// The orders delivered and not cancelled by the day, oldest first
+    fn orders(&self) -> Vec<OrderObject> {
+        self.ledger
+            .journal()
+            .orders(self.shop.elapsed_days)
+            .into_iter()
+            .map(|(id, order)| OrderObject { id, order })
+            .collect()
+    }
+}
+
+/// Milk and wool
+pub(crate) struct ProductsObject(Products);
+
+#[juniper::object(Context = Context, name = "Products")]
+impl ProductsObject {
+    /// In liters, as an exact decimal string such as `"1104.480"`
+    fn milk(&self) -> String {
+        self.0.milk().to_string()
+    }
+
+    /// In skins
+    fn wool(&self) -> i32 {
+        int(self.0.wool())
+    }
+}
+
+#[derive(GraphQLEnum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "Sex")]
+pub(crate) enum SexObject {
+    Female,
+    Male,
+}
+
+impl From<Sex> for SexObject {
+    fn from(sex: Sex) -> Self {
+        match sex {
+            Sex::Female => SexObject::Female,
+            Sex::Male => SexObject::Male,
+        }
+    }
+}
+
+/// A yak of a shop on a day
+pub(crate) struct YakObject {
+    yak: Yak,
+    calendar: Calendar,
+}
+
+#[juniper::object(Context = Context, name = "Yak")]
+impl YakObject {
+    fn name(&self) -> &str {
+        self.yak.name()
+    }
+
+    /// `null` if not known
+    fn sex(&self) -> Option<SexObject> {
+        self.yak.sex().map(SexObject::from)
+    }
+
+    fn age_days(&self) -> i32 {
+        int(self.yak.age_in_days())
+    }
+
+    /// The age in days when the yak was last shaved
+    fn age_last_shaved_days(&self) -> i32 {
+        int(self.yak.age_last_shaved_in_days())
+    }
+
+    fn alive(&self) -> bool {
+        self.yak.is_alive()
+    }
+
+    /// The day the yak can next be shaved, or `null` if it won't live that long
+    fn next_shave_day(&self) -> Option<i32> {
+        self.yak.next_shave_shop_day().map(int)
+    }
+
+    /// The ISO date of `nextShaveDay`, or `null` without a calendar start date
+    fn next_shave_date(&self) -> Option<String> {
+        date(
+            self.yak
+                .next_shave_shop_day()
+                .and_then(|day| self.calendar.date(day)),
+        )
+    }
+
+    /// The products produced by the yak since the shop opened
+    fn produced(&self) -> ProductsObject {
+        ProductsObject(self.yak.produced_products().clone())
+    }
+
+    fn shave_days(&self) -> Vec<i32> {
+        self.yak.shave_days().iter().copied().map(int).collect()
+    }
+
+    fn days_remaining(&self) -> i32 {
+        int(self.yak.days_remaining())
+    }
+
+    /// The products the yak will produce for the rest of its life
+    fn projected(&self) -> ProductsObject {
+        ProductsObject(self.yak.projected_products())
+    }
+}
+
+/// An order delivered by a shop
+pub(crate) struct OrderObject {
+    id: u32,
+    order: Order,
+}
+
+#[juniper::object(Context = Context, name = "Order")]
+impl OrderObject {
+    fn id(&self) -> i32 {
+        int(self.id)
+    }
+
+    fn customer(&self) -> &str {
+        &self.order.customer
+    }
+
+    /// The day the order was delivered on
+    fn day(&self) -> i32 {
+        int(self.order.day)
+    }
+
+    /// The products delivered
+    fn delivered(&self) -> ProductsObject {
+        ProductsObject(self.order.delivered.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use juniper::http::GraphQLRequest;
+    use serde_json::json;
+    use yakshop::ShopConfig;
+
+    fn context() -> Context {
+        let herd = "../tests/fixtures/valid_multi.xml".into();
+        Context {
+            store: Arc::new(Store::load(herd, None, ShopConfig::default()).unwrap()),
+        }
+    }
+
+    fn execute(context: &Context, query: &str) -> serde_json::Value {
+        let request = GraphQLRequest::new(query.to_string(), None, None);
+        serde_json::to_value(request.execute(&schema(), context)).unwrap()
+    }
+
+    #[test]
+    fn test_query_and_place_order() {
+        let context = context();
+
+        let response = execute(
+            &context,
+            r#"mutation {
+                placeOrder(day: "13", customer: "Medvedev", order: { milk: "1100.125", skins: 3 }) {
+                    id milk skins complete
+                }
+            }"#,
+        );
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "placeOrder": { "id": 1, "milk": "1100.125", "skins": 3, "complete": true }
+                }
+            })
+        );
+
+        let response = execute(
+            &context,
+            r#"{
+                shop(day: "13") {
+                    name day stock { milk wool }
+                    yak(name: "Betty-1") { sex ageDays nextShaveDay }
+                    orders { id customer delivered { milk wool } }
+                }
+            }"#,
+        );
+        assert_eq!(
+            response,
+            json!({
+                "data": {
+                    "shop": {
+                        "name": "valid_multi",
+                        "day": 13,
+                        "stock": { "milk": "4.355", "wool": 0 },
+                        "yak": { "sex": "FEMALE", "ageDays": 413, "nextShaveDay": 13 },
+                        "orders": [{
+                            "id": 1,
+                            "customer": "Medvedev",
+                            "delivered": { "milk": "1100.125", "wool": 3 }
+                        }]
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let context = context();
+
+        let response = execute(
+            &context,
+            r#"mutation {
+                placeOrder(day: "13", customer: "Medvedev", order: { milk: -1 }) { id }
+            }"#,
+        );
+        assert_eq!(
+            response["errors"][0]["extensions"],
+            json!({ "code": "invalid_field", "field": "order.milk" })
+        );
+
+        let response = execute(
+            &context,
+            r#"mutation {
+                placeOrder(day: "13", customer: "Medvedev", order: { milk: "a lot" }) { id }
+            }"#,
+        );
+        assert_eq!(
+            response["errors"][0]["extensions"],
+            json!({ "code": "invalid_field", "field": "order.milk" })
+        );
+
+        let response = execute(&context, r#"{ shop(name: "nowhere", day: "13") { day } }"#);
+        assert_eq!(
+            response["errors"][0]["extensions"],
+            json!({ "code": "not_found" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let routes = routes(&context().store);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/graphql")
+            .json(&json!({ "query": r#"{ shop(day: "13") { day } }"# }))
+            .reply(&routes)
+            .await;
+
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "data": { "shop": { "day": 13 } } }));
+    }
+}
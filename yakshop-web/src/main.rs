@@ -14,22 +14,76 @@
 #![allow(clippy::multiple_crate_versions)]
 
 use clap::Parser;
+use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::error::Error;
 use std::net::IpAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::{OpenApi, ToSchema};
 use warp::Filter;
 
-use yakshop::{Products, Shop, Yak};
+use yakshop::{
+    Calendar, Delivery, Distribution, Event, HerdEdit, HerdFormat, MonteCarlo, NaiveDate, Products,
+    ProfitAndLoss, Quantity, Sex, Shop, ShopConfig, ShopDay, SimulationReport, StochasticModel,
+    Yak, YakShopError,
+};
+
+#[cfg(feature = "sqlite")]
+mod database;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod openapi;
+mod store;
+mod updates;
+mod v2;
+
+use store::{Clock, Ledger, Shops, Store, COOPERATIVE};
+
+/// The longest `Idempotency-Key` header accepted for an order.
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// The largest request body accepted, in bytes, so a client can't make the server buffer any
+/// amount of data.
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// The location to the herd.xml file to use as a data source.
+    /// The location to the herd file (xml, json, yaml or toml) to use as a data source, or a
+    /// directory of herd files to serve as separate shops.
     #[arg(required = true)]
     pub herd: PathBuf,
 
+    /// The format of the herd file (default: detected from the file extension or content)
+    #[arg(long)]
+    pub input_format: Option<HerdFormat>,
+
+    /// The shop configuration file (default: `yakshop.toml` in the working directory, if present)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Reload the herd whenever the herd file (or a file in the herd directory) changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Pass the days on their own, pushing the changes to `/yakshop/updates`: `real-time` for a
+    /// day a day, or the number of seconds a day takes
+    #[arg(long)]
+    pub clock: Option<Clock>,
+
+    /// The bearer token required by the admin routes, which are disabled without one
+    #[arg(long, env = "YAKSHOP_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// The database file to keep the herds, orders and stock reported in, created if needed
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+
     #[arg(long, default_value = "3000")]
     pub http_port: u16,
 
@@ -37,32 +91,143 @@ pub struct Args {
     pub http_host: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct YakResponse {
     name: String,
     age: f64,
     age_last_shaved: f64,
+    /// The day the yak can next be shaved, or `None` if it won't live that long
+    next_shave_day: Option<u32>,
+    /// The date of `next_shave_day`, only present in calendar mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_shave_date: Option<NaiveDate>,
 }
 
-impl From<Yak> for YakResponse {
-    fn from(yak: Yak) -> Self {
+impl YakResponse {
+    fn new(yak: &Yak, calendar: Calendar) -> Self {
         YakResponse {
             name: yak.name().to_string(),
             age: yak.year_age(),
             age_last_shaved: yak.year_age_last_shaved(),
+            next_shave_day: yak.next_shave_shop_day(),
+            next_shave_date: yak.next_shave_shop_day().and_then(|day| calendar.date(day)),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct YakDetailResponse {
+    name: String,
+    age: f64,
+    age_last_shaved: f64,
+    alive: bool,
+    next_shave_day: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_shave_date: Option<NaiveDate>,
+    /// The products produced by the yak since the shop opened
+    produced: StockResponse,
+    shave_days: Vec<u32>,
+    days_remaining: u32,
+    /// The products the yak will produce for the rest of its life
+    projected: StockResponse,
+}
+
+impl YakDetailResponse {
+    fn new(yak: &Yak, calendar: Calendar) -> Self {
+        YakDetailResponse {
+            name: yak.name().to_string(),
+            age: yak.year_age(),
+            age_last_shaved: yak.year_age_last_shaved(),
+            alive: yak.is_alive(),
+            next_shave_day: yak.next_shave_shop_day(),
+            next_shave_date: yak.next_shave_shop_day().and_then(|day| calendar.date(day)),
+            produced: StockResponse::from(yak.produced_products()),
+            shave_days: yak.shave_days().to_vec(),
+            days_remaining: yak.days_remaining(),
+            projected: StockResponse::from(&yak.projected_products()),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct HerdResponse {
     herd: Vec<YakResponse>,
+    /// The date of the requested day, only present in calendar mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CooperativeYakResponse {
+    /// The shop the yak belongs to
+    shop: String,
+    #[serde(flatten)]
+    yak: YakResponse,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CooperativeHerdResponse {
+    herd: Vec<CooperativeYakResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CooperativeStockResponse {
+    #[schema(value_type = f64)]
+    milk: Quantity,
+    wool: u32,
+    /// The stock of each shop
+    shops: BTreeMap<String, StockResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct EventsResponse<'a> {
+    /// The events up to the requested day, oldest first
+    #[schema(value_type = Vec<Object>)]
+    events: Vec<&'a Event>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReloadResponse {
+    /// The names of the shops served after the reload
+    shops: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct NewYakRequest {
+    name: String,
+    /// In yak years
+    #[schema(value_type = f64)]
+    age: Quantity,
+    /// `f` or `m`
+    #[schema(value_type = Option<String>)]
+    sex: Option<Sex>,
 }
 
-#[derive(Serialize)]
+/// The changes to a yak, leaving out what stays the same.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct YakPatchRequest {
+    name: Option<String>,
+    /// In yak years
+    #[schema(value_type = Option<f64>)]
+    age: Option<Quantity>,
+}
+
+#[derive(Serialize, ToSchema)]
 struct StockResponse {
-    milk: f64,
+    #[schema(value_type = f64)]
+    milk: Quantity,
     wool: u32,
+    /// The date of the requested day, only present in calendar mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
 }
 
 impl From<&Products> for StockResponse {
@@ -70,140 +235,1557 @@ impl From<&Products> for StockResponse {
         StockResponse {
             milk: products.milk(),
             wool: products.wool(),
+            date: None,
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct OrderRequest {
-    // Dead at present since it is never referenced
-    #[allow(dead_code)]
     customer: String,
     order: OrderItems,
 }
 
-#[derive(Deserialize, Serialize)]
+/// The products ordered, taken as plain values so `validate` can tell which one is wrong.
+#[derive(Deserialize, ToSchema)]
 struct OrderItems {
+    /// In liters
+    #[schema(value_type = Option<f64>)]
+    milk: Option<Liters>,
+    skins: Option<f64>,
+}
+
+/// An amount of milk, as a number or as an exact decimal string such as `"1100.500"`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum Liters {
+    Number(f64),
+    Exact(String),
+}
+
+impl Liters {
+    fn quantity(&self) -> Result<Quantity, YakShopError> {
+        match self {
+            Liters::Number(liters) => Quantity::try_from_f64(*liters),
+            Liters::Exact(liters) => liters.parse(),
+        }
+    }
+}
+
+impl OrderItems {
+    /// The milk and skins ordered, which must be finite, non-negative amounts (whole ones for
+    /// skins), with at least one of them more than zero.
+    fn validate(&self) -> Result<(Option<Quantity>, Option<u32>), ApiError> {
+        let milk = self
+            .milk
+            .as_ref()
+            .map(Liters::quantity)
+            .transpose()
+            .map_err(|err| ApiError::invalid_field("order.milk", err.to_string()))?;
+
+        let skins = self
+            .skins
+            .map(|skins| {
+                if !skins.is_finite() || skins < 0.0 || skins.fract() != 0.0 {
+                    return Err("Skins must be a whole, non-negative number");
+                }
+                if skins > f64::from(u32::MAX) {
+                    return Err("Too many skins");
+                }
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                Ok(skins as u32)
+            })
+            .transpose()
+            .map_err(|message| ApiError::invalid_field("order.skins", message.to_string()))?;
+
+        let ordered = |milk: Option<Quantity>, skins: Option<u32>| {
+            milk.is_some_and(|milk| !milk.is_zero()) || skins.is_some_and(|skins| skins > 0)
+        };
+        if !ordered(milk, skins) {
+            return Err(ApiError::invalid_field(
+                "order",
+                "An order needs some milk or skins".to_string(),
+            ));
+        }
+
+        Ok((milk, skins))
+    }
+}
+
+/// The products delivered for an order.
+#[derive(Serialize, ToSchema)]
+struct DeliveryResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
-    milk: Option<f64>,
+    #[schema(value_type = Option<f64>)]
+    milk: Option<Quantity>,
     #[serde(skip_serializing_if = "Option::is_none")]
     skins: Option<u32>,
 }
 
-impl From<(Option<f64>, Option<u32>)> for OrderItems {
-    fn from((milk, skins): (Option<f64>, Option<u32>)) -> Self {
-        OrderItems { milk, skins }
+impl From<(Option<Quantity>, Option<u32>)> for DeliveryResponse {
+    fn from((milk, skins): (Option<Quantity>, Option<u32>)) -> Self {
+        DeliveryResponse { milk, skins }
     }
 }
 
-/// Main entry point for the web server application.
-///
-/// The server accepts a single command line argument, the path to the herd.xml file to use as a data source.
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+/// The body of every error reply.
+#[derive(Serialize, Debug, ToSchema)]
+struct ApiError {
+    #[serde(skip)]
+    status: warp::http::StatusCode,
+    /// What went wrong, for clients to act on, such as `out_of_stock` or `not_found`
+    code: &'static str,
+    message: String,
+    /// The field of the request body which is invalid, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'static str>,
+}
 
-    let http_host: IpAddr = args.http_host.parse()?;
-    let http_port = args.http_port;
+impl ApiError {
+    fn new(status: warp::http::StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            field: None,
+        }
+    }
 
-    let shop = Shop::try_from(&args.herd)?;
+    fn invalid_field(field: &'static str, message: String) -> Self {
+        ApiError {
+            field: Some(field),
+            ..ApiError::new(
+                warp::http::StatusCode::BAD_REQUEST,
+                "invalid_field",
+                message,
+            )
+        }
+    }
 
-    let home_page = warp::path::end()
+    fn reply(&self) -> warp::reply::WithStatus<warp::reply::Json> {
+        warp::reply::with_status(warp::reply::json(self), self.status)
+    }
+}
+
+impl From<YakShopError> for ApiError {
+    fn from(err: YakShopError) -> Self {
+        ApiError::from(&err)
+    }
+}
+
+/// Uses `422 Unprocessable Entity` for totals which overflowed as the request was valid but the
+/// stock can't be represented, and `500 Internal Server Error` when an edited herd can't be
+/// written.
+impl From<&YakShopError> for ApiError {
+    fn from(err: &YakShopError) -> Self {
+        let (status, code) = match err {
+            YakShopError::Overflow(_) => (warp::http::StatusCode::UNPROCESSABLE_ENTITY, "overflow"),
+            YakShopError::YakNotFound(_) | YakShopError::OrderNotFound(_) => {
+                (warp::http::StatusCode::NOT_FOUND, "not_found")
+            }
+            YakShopError::ConfigFileWriteError(_) | YakShopError::StorageError(_) => (
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+            _ => (warp::http::StatusCode::BAD_REQUEST, "bad_request"),
+        };
+
+        ApiError::new(status, code, err.to_string())
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct CostsResponse {
+    #[schema(value_type = f64)]
+    feed: Quantity,
+    #[schema(value_type = f64)]
+    care: Quantity,
+    #[schema(value_type = f64)]
+    total: Quantity,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ProfitAndLossResponse {
+    /// The products sold by the orders delivered up to the requested day
+    sold: StockResponse,
+    #[schema(value_type = f64)]
+    revenue: Quantity,
+    costs: CostsResponse,
+    /// Negative for a loss
+    profit: f64,
+    /// The date of the requested day, only present in calendar mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+}
+
+impl From<&ProfitAndLoss> for ProfitAndLossResponse {
+    fn from(pnl: &ProfitAndLoss) -> Self {
+        ProfitAndLossResponse {
+            sold: StockResponse::from(&pnl.sold),
+            revenue: pnl.revenue,
+            costs: CostsResponse {
+                feed: pnl.expenses.feed,
+                care: pnl.expenses.care,
+                total: pnl.costs(),
+            },
+            profit: pnl.profit(),
+            date: None,
+        }
+    }
+}
+
+// Not imported, as the generated docs name it in full
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SimulationQuery {
+    /// The number of simulations, 100 by default and at most 10000
+    #[serde(default = "SimulationQuery::default_runs")]
+    runs: u32,
+    seed: Option<u64>,
+    milk_noise: Option<f64>,
+    illness_rate: Option<f64>,
+    mortality_rate: Option<f64>,
+}
+
+impl SimulationQuery {
+    fn default_runs() -> u32 {
+        100
+    }
+
+    fn stochastic_model(&self) -> StochasticModel {
+        let default = StochasticModel::default();
+        StochasticModel {
+            milk_noise: self.milk_noise.unwrap_or(default.milk_noise),
+            illness_rate: self.illness_rate.unwrap_or(default.illness_rate),
+            mortality_rate: self.mortality_rate.unwrap_or(default.mortality_rate),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct DistributionResponse {
+    mean: f64,
+    p5: f64,
+    p50: f64,
+    p95: f64,
+}
+
+impl From<&Distribution> for DistributionResponse {
+    fn from(distribution: &Distribution) -> Self {
+        DistributionResponse {
+            mean: distribution.mean(),
+            p5: distribution.percentile(5.0),
+            p50: distribution.percentile(50.0),
+            p95: distribution.percentile(95.0),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct SimulationResponse {
+    runs: u32,
+    seed: u64,
+    milk: DistributionResponse,
+    wool: DistributionResponse,
+    surviving_yaks: DistributionResponse,
+}
+
+impl From<&SimulationReport> for SimulationResponse {
+    fn from(report: &SimulationReport) -> Self {
+        SimulationResponse {
+            runs: report.runs,
+            seed: report.seed,
+            milk: DistributionResponse::from(&report.milk),
+            wool: DistributionResponse::from(&report.wool),
+            surviving_yaks: DistributionResponse::from(&report.surviving_yaks),
+        }
+    }
+}
+
+/// Picks the id of the shop named in the next path segment.
+fn named_shop_id(
+    store: Arc<Store>,
+) -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    warp::path::param::<String>().and_then(move |id: String| {
+        let shops = store.shops();
+        async move {
+            let id = percent_decode_str(&id).decode_utf8_lossy().into_owned();
+            match shops.cooperative.shop(&id) {
+                Some(_) => Ok(id),
+                None => Err(warp::reject::not_found()),
+            }
+        }
+    })
+}
+
+/// Picks the id of the shop served without a shop id, if there is one.
+fn default_shop_id(
+    store: Arc<Store>,
+) -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    warp::any().and_then(move || {
+        let shops = store.shops();
+        async move {
+            shops
+                .default_shop
+                .clone()
+                .ok_or_else(warp::reject::not_found)
+        }
+    })
+}
+
+/// Picks the ledger of the shop with the id extracted by `id`.
+fn shop_by_id<F>(
+    id: F,
+    store: Arc<Store>,
+) -> impl Filter<Extract = (Ledger,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (String,), Error = warp::Rejection> + Clone,
+{
+    id.and_then(move |id: String| {
+        let shops = store.shops();
+        async move { shops.get(&id).ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Picks the shop named in the next path segment.
+fn named_shop(
+    store: Arc<Store>,
+) -> impl Filter<Extract = (Ledger,), Error = warp::Rejection> + Clone {
+    shop_by_id(named_shop_id(store.clone()), store)
+}
+
+/// Picks the shop served without a shop id, if there is one.
+fn default_shop(
+    store: Arc<Store>,
+) -> impl Filter<Extract = (Ledger,), Error = warp::Rejection> + Clone {
+    shop_by_id(default_shop_id(store.clone()), store)
+}
+
+/// Rejects requests to the admin routes without a valid token.
+#[derive(Debug)]
+enum AdminRejection {
+    /// The server was started without an admin token
+    Disabled,
+    Unauthorized,
+}
+
+impl warp::reject::Reject for AdminRejection {}
+
+/// Lets the request through if it has `Authorization: Bearer <token>` with the admin token.
+fn authorized(
+    token: Option<Arc<str>>,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let token = token.clone();
+            async move {
+                let Some(token) = token else {
+                    return Err(warp::reject::custom(AdminRejection::Disabled));
+                };
+
+                let given = header
+                    .as_deref()
+                    .and_then(|header| header.strip_prefix("Bearer "));
+                match given {
+                    Some(given) if constant_time_eq(given.as_bytes(), token.as_bytes()) => Ok(()),
+                    _ => Err(warp::reject::custom(AdminRejection::Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Compares the tokens without stopping at the first difference, so the time taken does not tell
+/// how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Replies to rejected requests with an `ApiError`, asking for the admin token where it is
+/// missing or wrong.
+#[allow(clippy::unused_async)]
+async fn handle_rejection(rejection: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
+    use warp::http::StatusCode;
+    use warp::Reply;
+
+    let error = if rejection.is_not_found() {
+        ApiError::new(StatusCode::NOT_FOUND, "not_found", "Not found")
+    } else if let Some(rejection) = rejection.find::<AdminRejection>() {
+        let error = match rejection {
+            AdminRejection::Disabled => ApiError::new(
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                "The admin routes are disabled, as no admin token is set",
+            ),
+            AdminRejection::Unauthorized => ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "A valid admin token is required",
+            ),
+        };
+        return Ok(
+            warp::reply::with_header(error.reply(), "www-authenticate", "Bearer").into_response(),
+        );
+    } else if let Some(err) = rejection.find::<warp::filters::body::BodyDeserializeError>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "invalid_body", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::UnsupportedMediaType>() {
+        ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::PayloadTooLarge>() {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::LengthRequired>() {
+        ApiError::new(
+            StatusCode::LENGTH_REQUIRED,
+            "length_required",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::InvalidQuery>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "bad_request", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::MissingHeader>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "bad_request", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::MethodNotAllowed>() {
+        ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::InvalidHeader>() {
+        ApiError::new(StatusCode::BAD_REQUEST, "bad_request", err.to_string())
+    } else {
+        eprintln!("Unhandled rejection: {rejection:?}");
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Something went wrong",
+        )
+    };
+
+    Ok(error.reply().into_response())
+}
+
+/// A JSON request body, which must state its length and be at most `MAX_BODY_LENGTH` bytes.
+fn json_body<T: serde::de::DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Copy {
+    warp::body::content_length_limit(MAX_BODY_LENGTH).and(warp::body::json())
+}
+
+/// The routes of a single shop, picked by `shop`.
+fn shop_routes<F>(
+    shop: F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (Ledger,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+{
+    let stock = shop
+        .clone()
+        .and(warp::path!("stock" / ShopDay))
         .and(warp::get())
-        .map(|| warp::reply::html(include_str!("../../static/index.html")));
+        .and_then(|ledger: Ledger, days: ShopDay| get_stock(days, ledger));
+
+    let herd = shop
+        .clone()
+        .and(warp::path!("herd" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_herd(days, ledger));
+
+    let yak = shop
+        .clone()
+        .and(warp::path!("herd" / ShopDay / String))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay, name: String| get_yak(days, name, ledger));
+
+    let order = shop
+        .clone()
+        .and(warp::path!("order" / ShopDay))
+        .and(warp::post())
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(json_body())
+        .and_then(
+            |ledger: Ledger, days: ShopDay, key: Option<String>, order: OrderRequest| {
+                post_order(days, key, order, ledger)
+            },
+        );
+
+    let cancel = shop
+        .clone()
+        .and(warp::path!("order" / ShopDay / u32))
+        .and(warp::delete())
+        .and_then(|ledger: Ledger, days: ShopDay, id: u32| delete_order(days, id, ledger));
+
+    let events = shop
+        .clone()
+        .and(warp::path!("events" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_events(days, ledger));
+
+    let pnl = shop
+        .clone()
+        .and(warp::path!("pnl" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_pnl(days, ledger));
+
+    let updates = shop
+        .clone()
+        .and(warp::path!("updates"))
+        .and(warp::get())
+        .map(|ledger: Ledger| updates::get_updates(&ledger));
+
+    let simulation = shop
+        .and(warp::path!("simulation" / ShopDay))
+        .and(warp::get())
+        .and(warp::query::<SimulationQuery>())
+        .and_then(|ledger: Ledger, days: ShopDay, query: SimulationQuery| {
+            let shop = ledger.journal().opened().clone();
+            get_simulation(days, query, shop)
+        });
+
+    stock
+        .or(herd)
+        .or(yak)
+        .or(order)
+        .or(cancel)
+        .or(events)
+        .or(pnl)
+        .or(updates)
+        .or(simulation)
+}
+
+/// The admin routes to edit the herd of a single shop, picked by `shop_id`.
+fn herd_admin_routes<F>(
+    shop_id: F,
+    store: &Arc<Store>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (String,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+{
+    let add = {
+        let store = store.clone();
+        shop_id
+            .clone()
+            .and(warp::path!("herd"))
+            .and(warp::post())
+            .and(json_body())
+            .and_then(move |id: String, yak: NewYakRequest| post_yak(store.clone(), id, yak))
+    };
+
+    let edit = {
+        let store = store.clone();
+        shop_id
+            .clone()
+            .and(warp::path!("herd" / String))
+            .and(warp::patch())
+            .and(json_body())
+            .and_then(move |id: String, name: String, patch: YakPatchRequest| {
+                patch_yak(store.clone(), id, name, patch)
+            })
+    };
 
+    let retire = {
+        let store = store.clone();
+        shop_id
+            .and(warp::path!("herd" / String))
+            .and(warp::delete())
+            .and_then(move |id: String, name: String| delete_yak(store.clone(), id, name))
+    };
+
+    add.or(edit).or(retire)
+}
+
+/// The routes of the cooperative view, which combines all shops.
+fn cooperative_routes(
+    store: &Arc<Store>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let stock = {
-        let shop = shop.clone();
-        warp::path!("yakshop" / "stock" / u32)
+        let store = store.clone();
+        warp::path!("stock" / ShopDay)
             .and(warp::get())
-            .and_then(move |days: u32| {
-                let shop = shop.clone();
-                get_stock(days, shop)
-            })
+            .and_then(move |days: ShopDay| get_cooperative_stock(days, store.shops()))
     };
 
     let herd = {
-        let shop = shop.clone();
-        warp::path!("yakshop" / "herd" / u32)
+        let store = store.clone();
+        warp::path!("herd" / ShopDay)
             .and(warp::get())
-            .and_then(move |days: u32| {
-                let shop = shop.clone();
-                get_herd(days, shop)
-            })
+            .and_then(move |days: ShopDay| get_cooperative_herd(days, store.shops()))
     };
 
-    let order = {
-        let shop = shop.clone();
+    warp::path(COOPERATIVE).and(stock.or(herd))
+}
+
+/// All routes served, with the admin routes requiring `admin_token`.
+fn routes(
+    store: &Arc<Store>,
+    admin_token: Option<Arc<str>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
+    let home_page = warp::path::end()
+        .and(warp::get())
+        .map(|| warp::reply::html(include_str!("../../static/index.html")));
+
+    let openapi = openapi::ApiDoc::openapi();
+    let openapi = warp::path!("openapi.json")
+        .and(warp::get())
+        .map(move || warp::reply::json(&openapi));
 
-        warp::path!("yakshop" / "order" / u32)
+    let reload = {
+        let store = store.clone();
+        warp::path!("reload")
             .and(warp::post())
-            .and(warp::body::json())
-            .and_then(move |days: u32, order: OrderRequest| {
-                let shop = shop.clone();
+            .and_then(move || post_reload(store.clone()))
+    };
 
-                post_order(days, order, shop)
-            })
+    let admin = warp::path("admin").and(authorized(admin_token)).and(
+        reload
+            .or(herd_admin_routes(default_shop_id(store.clone()), store))
+            .or(herd_admin_routes(named_shop_id(store.clone()), store)),
+    );
+
+    // The first version is also served without a version, as it was before there was a second
+    let v1 = cooperative_routes(store)
+        .or(shop_routes(default_shop(store.clone())))
+        .or(shop_routes(named_shop(store.clone())));
+    let v2 =
+        v2::shop_routes(default_shop(store.clone())).or(v2::shop_routes(named_shop(store.clone())));
+
+    let routes = home_page
+        .or(openapi)
+        .or(admin)
+        .or(warp::path("yakshop").and(v1.clone()))
+        .or(warp::path!("v1" / "yakshop" / ..).and(v1))
+        .or(warp::path!("v2" / "yakshop" / ..).and(v2));
+
+    #[cfg(feature = "graphql")]
+    let routes = routes.or(graphql::routes(store));
+
+    routes.recover(handle_rejection)
+}
+
+/// Main entry point for the web server application.
+///
+/// The server accepts a single command line argument, the path to the herd.xml file to use as a data
+/// source, or to a directory of herd files to serve as separate shops.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let http_host: IpAddr = args.http_host.parse()?;
+    let http_port = args.http_port;
+
+    let config = ShopConfig::load(args.config.as_deref())?;
+    let store = Store::load(args.herd, args.input_format, config)?;
+    #[cfg(feature = "sqlite")]
+    let store = match &args.database {
+        Some(path) => store.with_database(database::Database::open(path)?)?,
+        None => store,
     };
+    let store = Arc::new(store);
 
     println!("Starting server on http://{http_host}:{http_port}");
+    for id in store.shops().cooperative.shops().keys() {
+        println!("Serving shop {id} on http://{http_host}:{http_port}/yakshop/{id}/");
+    }
+
+    if args.watch {
+        tokio::spawn(store.clone().watch(Duration::from_secs(1)));
+    }
+    if let Some(clock) = args.clock {
+        println!("Passing a day every {:?}", clock.day_length());
+        tokio::spawn(store.clone().run_clock(clock));
+    }
 
-    let routes = home_page.or(stock).or(herd).or(order);
+    let routes = routes(&store, args.admin_token.map(Arc::from));
     warp::serve(routes).run((http_host, http_port)).await;
 
     Ok(())
 }
 
-#[allow(clippy::unused_async)]
+/// The stock of the shop on a day
+///
+/// The milk and wool produced up to the day, less the orders delivered by then.
+#[utoipa::path(
+    get,
+    path = "/yakshop/stock/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The stock", body = StockResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 422, description = "The stock overflowed", body = ApiError),
+    ),
+)]
 #[allow(clippy::missing_errors_doc)]
-async fn get_stock(day: u32, mut shop: Shop) -> Result<impl warp::Reply, Infallible> {
-    shop.step_days(day);
+async fn get_stock(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let (_, stock, date) = match blocking(move || stock(&ledger, day)).await {
+        Ok(stock) => stock,
+        Err(err) => return Ok(err.reply()),
+    };
 
-    Ok(warp::reply::json(&StockResponse::from(
-        &shop.produced_products,
-    )))
+    Ok(warp::reply::with_status(
+        warp::reply::json(&StockResponse {
+            date,
+            ..StockResponse::from(&stock)
+        }),
+        warp::http::StatusCode::OK,
+    ))
 }
 
-#[allow(clippy::unused_async)]
+/// The herd of the shop on a day
+#[utoipa::path(
+    get,
+    path = "/yakshop/herd/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The yaks, including the dead ones", body = HerdResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+    ),
+)]
 #[allow(clippy::missing_errors_doc)]
-async fn get_herd(day: u32, mut shop: Shop) -> Result<impl warp::Reply, Infallible> {
-    shop.step_days(day);
+async fn get_herd(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let shop = match blocking(move || replay(&ledger, day)).await {
+        Ok(shop) => shop,
+        Err(err) => return Ok(err.reply()),
+    };
 
-    let yaks = shop.yaks().iter().map(|yak| YakResponse::from(yak.clone()));
-    Ok(warp::reply::json(&HerdResponse {
-        herd: yaks.collect(),
-    }))
+    let calendar = shop.config().calendar;
+    let yaks = shop
+        .yaks()
+        .iter()
+        .map(|yak| YakResponse::new(yak, calendar));
+    Ok(warp::reply::with_status(
+        warp::reply::json(&HerdResponse {
+            herd: yaks.collect(),
+            date: shop.date(),
+        }),
+        warp::http::StatusCode::OK,
+    ))
 }
 
-#[allow(clippy::unused_async)]
+/// A yak of the shop on a day
+///
+/// The yak's lifetime production, shave days, days remaining and projected production for the
+/// rest of its life.
+#[utoipa::path(
+    get,
+    path = "/yakshop/herd/{day}/{name}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode"), ("name" = String, Path, description = "The name of the yak")),
+    responses(
+        (status = 200, description = "The yak", body = YakDetailResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 404, description = "There is no such yak", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_yak(
+    day: ShopDay,
+    name: String,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let shop = match blocking(move || replay(&ledger, day)).await {
+        Ok(shop) => shop,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let name = percent_decode_str(&name).decode_utf8_lossy();
+
+    let result = match shop.yak(&name) {
+        Some(yak) => warp::reply::with_status(
+            warp::reply::json(&YakDetailResponse::new(yak, shop.config().calendar)),
+            warp::http::StatusCode::OK,
+        ),
+        None => error_reply(&YakShopError::YakNotFound(name.into_owned())),
+    };
+
+    Ok(result)
+}
+
+/// Places an order on a day
+///
+/// Each product is delivered in full or not at all, and taken from the stock. Orders can't be
+/// placed on a day the shop has passed.
+#[utoipa::path(
+    post,
+    path = "/yakshop/order/{day}",
+    params(
+        ("day" = String, Path, description = "The day number, or an ISO date in calendar mode"),
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "A unique key for the order, so retries with it aren't delivered twice"
+        ),
+    ),
+    request_body(
+        content = OrderRequest,
+        example = json!({"customer": "Medvedev", "order": {"milk": 1100, "skins": 3}}),
+    ),
+    responses(
+        (status = 201, description = "The order was delivered in full", body = DeliveryResponse),
+        (status = 206, description = "Only the milk or the skins were delivered", body = DeliveryResponse),
+        (status = 400, description = "The order or day is invalid", body = ApiError),
+        (status = 409, description = "None of the order is in stock", body = ApiError),
+    ),
+)]
 #[allow(clippy::missing_errors_doc)]
 async fn post_order(
-    day: u32,
+    day: ShopDay,
+    key: Option<String>,
     order: OrderRequest,
-    mut shop: Shop,
+    ledger: Ledger,
 ) -> Result<impl warp::Reply, Infallible> {
-    shop.step_days(day);
+    let delivery = blocking(move || place_order(day, key.as_deref(), &order, &ledger)).await;
+    let result = match delivery {
+        Ok((delivery, status)) => warp::reply::with_status(
+            warp::reply::json(&DeliveryResponse::from((delivery.milk, delivery.wool))),
+            status,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
 
-    // Consume the possible products from the shop
-    let consumed_products = shop.consume_products(order.order.milk, order.order.skins);
-    let response = OrderItems::from(consumed_products);
+/// Validates an order and consumes what it can from the stock of the day, unless it is a retry of
+/// an order placed with the same idempotency key. Returns the delivery with the status to reply
+/// with.
+fn place_order(
+    day: ShopDay,
+    key: Option<&str>,
+    order: &OrderRequest,
+    ledger: &Ledger,
+) -> Result<(Delivery, warp::http::StatusCode), ApiError> {
+    if let Some(key) = key {
+        if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(ApiError::new(
+                warp::http::StatusCode::BAD_REQUEST,
+                "bad_request",
+                format!("An idempotency key has 1 to {MAX_IDEMPOTENCY_KEY_LENGTH} characters"),
+            ));
+        }
+    }
+
+    let (milk, skins) = order.order.validate()?;
+    if order.customer.trim().is_empty() {
+        return Err(ApiError::invalid_field(
+            "customer",
+            "An order needs a customer".to_string(),
+        ));
+    }
+
+    let delivery = ledger
+        .place_order(key, day, &order.customer, milk, skins)
+        .map_err(|err| ApiError::from(&err))?;
 
     // After consuming the order:
     // - If we can deliver everything, 201,
     // - If we can delivery only some goods, 206,
-    // - If we can't deliver anything, 404
-    let result = match consumed_products {
-        (Some(_), Some(_)) => warp::reply::with_status(
-            warp::reply::json(&response),
-            warp::http::StatusCode::CREATED,
+    // - If we can't deliver anything, 409
+    let status = match (delivery.milk, delivery.wool) {
+        (Some(_), Some(_)) => warp::http::StatusCode::CREATED,
+        (None, Some(_)) | (Some(_), None) => warp::http::StatusCode::PARTIAL_CONTENT,
+        (None, None) => {
+            return Err(ApiError::new(
+                warp::http::StatusCode::CONFLICT,
+                "out_of_stock",
+                "There is not enough stock to deliver any of the order",
+            ))
+        }
+    };
+
+    Ok((delivery, status))
+}
+
+/// Cancels an order on a day
+///
+/// The products of the order go back to the stock. Orders are numbered from 1 as they are placed.
+#[utoipa::path(
+    delete,
+    path = "/yakshop/order/{day}/{id}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode"), ("id" = u32, Path, description = "The number of the order")),
+    responses(
+        (status = 200, description = "The products returned to the stock", body = DeliveryResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 404, description = "There is no such order", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn delete_order(
+    day: ShopDay,
+    id: u32,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let cancelled = blocking(move || ledger.cancel_order(day, id)).await;
+
+    let result = match cancelled {
+        Ok(order) => warp::reply::with_status(
+            warp::reply::json(&DeliveryResponse {
+                milk: Some(order.delivered.milk()),
+                skins: Some(order.delivered.wool()),
+            }),
+            warp::http::StatusCode::OK,
         ),
-        (None, Some(_)) | (Some(_), None) => warp::reply::with_status(
-            warp::reply::json(&response),
-            warp::http::StatusCode::PARTIAL_CONTENT,
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// What happened up to a day
+///
+/// The days passed, shaves, deaths, orders, cancellations and herd edits, oldest first.
+#[utoipa::path(
+    get,
+    path = "/yakshop/events/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The events", body = EventsResponse<'static>),
+        (status = 400, description = "The day is invalid", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_events(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let result = blocking(move || {
+        ledger.read(day, |journal, day| {
+            Ok(warp::reply::with_status(
+                warp::reply::json(&EventsResponse {
+                    events: journal.events_until(day),
+                    date: journal.date(day),
+                }),
+                warp::http::StatusCode::OK,
+            ))
+        })
+    })
+    .await;
+
+    Ok(result.unwrap_or_else(|err| err.reply()))
+}
+
+/// The profit and loss of the shop up to a day
+///
+/// The revenue of the orders delivered up to the day, the feed and care costs of the herd and the
+/// profit (negative for a loss).
+#[utoipa::path(
+    get,
+    path = "/yakshop/pnl/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The profit and loss", body = ProfitAndLossResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 422, description = "The stock overflowed", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_pnl(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let result = match blocking(move || profit_and_loss(&ledger, day)).await {
+        Ok((pnl, date)) => warp::reply::with_status(
+            warp::reply::json(&ProfitAndLossResponse {
+                date,
+                ..ProfitAndLossResponse::from(&pnl)
+            }),
+            warp::http::StatusCode::OK,
         ),
-        (None, None) => {
-            warp::reply::with_status(warp::reply::json(&()), warp::http::StatusCode::NOT_FOUND)
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// Simulates the stock of the shop on a day
+///
+/// Runs a number of stochastic simulations of the herd as it opened, and returns the mean and
+/// percentiles of the stock.
+#[utoipa::path(
+    get,
+    path = "/yakshop/simulation/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode"), SimulationQuery),
+    responses(
+        (status = 200, description = "The outcome of the simulations", body = SimulationResponse),
+        (status = 400, description = "The day, the model or the number of runs is invalid", body = ApiError),
+        (status = 422, description = "The stock overflowed", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_simulation(
+    day: ShopDay,
+    query: SimulationQuery,
+    shop: Shop,
+) -> Result<impl warp::Reply, Infallible> {
+    let day = match shop.day(day) {
+        Ok(day) => day,
+        Err(err) => return Ok(error_reply(&err)),
+    };
+
+    let seed = query.seed.unwrap_or_else(rand::random);
+
+    let model = query.stochastic_model();
+    let report = blocking(move || MonteCarlo::new(model, query.runs, seed)?.run(&shop, day)).await;
+
+    let result = match report {
+        Ok(report) => warp::reply::with_status(
+            warp::reply::json(&SimulationResponse::from(&report)),
+            warp::http::StatusCode::OK,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// The stock of all shops together on a day
+#[utoipa::path(
+    get,
+    path = "/yakshop/cooperative/stock/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The stock, and the stock of each shop", body = CooperativeStockResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 422, description = "The stock overflowed", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_cooperative_stock(
+    day: ShopDay,
+    shops: Arc<Shops>,
+) -> Result<impl warp::Reply, Infallible> {
+    let cooperative = Arc::clone(&shops);
+    let stock = blocking(move || {
+        let day = cooperative.cooperative.day(day)?;
+        let stocks = cooperative
+            .ledgers()
+            .iter()
+            .map(|(id, ledger)| {
+                let stock = ledger.read(ShopDay::Number(day), |journal, day| journal.stock(day))?;
+                Ok((id.clone(), stock.unwrap_or_default()))
+            })
+            .collect::<Result<BTreeMap<_, _>, YakShopError>>()?;
+
+        let total = stocks
+            .values()
+            .try_fold(Products::default(), |total, stock| total.checked_add(stock))?;
+        Ok::<_, YakShopError>((day, total, stocks))
+    })
+    .await;
+
+    let result = match stock {
+        Ok((day, stock, stocks)) => warp::reply::with_status(
+            warp::reply::json(&CooperativeStockResponse {
+                milk: stock.milk(),
+                wool: stock.wool(),
+                shops: stocks
+                    .iter()
+                    .map(|(id, stock)| (id.clone(), StockResponse::from(stock)))
+                    .collect(),
+                date: cooperative_date(&shops, day),
+            }),
+            warp::http::StatusCode::OK,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// The yaks of every shop on a day
+#[utoipa::path(
+    get,
+    path = "/yakshop/cooperative/herd/{day}",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The yaks", body = CooperativeHerdResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+async fn get_cooperative_herd(
+    day: ShopDay,
+    shops: Arc<Shops>,
+) -> Result<impl warp::Reply, Infallible> {
+    let cooperative = Arc::clone(&shops);
+    let herds = blocking(move || {
+        let day = cooperative.cooperative.day(day)?;
+        let herds = cooperative
+            .ledgers()
+            .iter()
+            .map(|(id, ledger)| Ok((id.clone(), replay(ledger, ShopDay::Number(day))?)))
+            .collect::<Result<Vec<_>, YakShopError>>()?;
+        Ok::<_, YakShopError>((day, herds))
+    })
+    .await;
+
+    let (day, herds) = match herds {
+        Ok(herds) => herds,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let herd = herds.iter().flat_map(|(id, shop)| {
+        shop.yaks().iter().map(|yak| CooperativeYakResponse {
+            shop: id.clone(),
+            yak: YakResponse::new(yak, shop.config().calendar),
+        })
+    });
+    Ok(warp::reply::with_status(
+        warp::reply::json(&CooperativeHerdResponse {
+            herd: herd.collect(),
+            date: cooperative_date(&shops, day),
+        }),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+/// The date of `day` in the cooperative, only in calendar mode. The shops share their calendar.
+fn cooperative_date(shops: &Shops, day: u32) -> Option<NaiveDate> {
+    shops
+        .cooperative
+        .shops()
+        .values()
+        .next()
+        .and_then(|shop| shop.config().calendar.date(day))
+}
+
+/// Loads the herd file again
+///
+/// If the herd is invalid, or can't deliver the orders already placed, the current herd keeps
+/// serving.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "The shops served", body = ReloadResponse),
+        (status = 400, description = "The herd is invalid", body = ApiError),
+        (status = 401, description = "The admin token is missing or wrong", body = ApiError),
+        (status = 403, description = "The admin routes are disabled", body = ApiError),
+    ),
+)]
+#[allow(clippy::unused_async)]
+#[allow(clippy::missing_errors_doc)]
+async fn post_reload(store: Arc<Store>) -> Result<impl warp::Reply, Infallible> {
+    let result = match store.reload() {
+        Ok(shops) => warp::reply::with_status(
+            warp::reply::json(&ReloadResponse {
+                shops: shops.cooperative.shops().keys().cloned().collect(),
+            }),
+            warp::http::StatusCode::OK,
+        ),
+        Err(err) => {
+            eprintln!("Failed to reload the herd, keeping the current herd: {err}");
+            error_reply(&err)
+        }
+    };
+
+    Ok(result)
+}
+
+/// Adds a yak to the herd
+///
+/// The yak joins the herd today, and the change is recorded in the journal of the shop.
+#[utoipa::path(
+    post,
+    path = "/admin/herd",
+    security(("admin_token" = [])),
+    request_body(
+        content = NewYakRequest,
+        example = json!({"name": "Dolma", "age": 2.5, "sex": "f"}),
+    ),
+    responses(
+        (status = 201, description = "The yak added", body = YakResponse),
+        (status = 400, description = "The yak is invalid", body = ApiError),
+        (status = 401, description = "The admin token is missing or wrong", body = ApiError),
+        (status = 403, description = "The admin routes are disabled", body = ApiError),
+        (status = 500, description = "The herd file can't be written", body = ApiError),
+    ),
+)]
+#[allow(clippy::unused_async)]
+#[allow(clippy::missing_errors_doc)]
+async fn post_yak(
+    store: Arc<Store>,
+    id: String,
+    yak: NewYakRequest,
+) -> Result<impl warp::Reply, Infallible> {
+    let result = store.edit(&id, |journal| {
+        journal.edit_herd(HerdEdit::Added {
+            name: yak.name.clone(),
+            age: yak.age,
+            sex: yak.sex,
+        })?;
+        herd_yak(journal.shop(), &yak.name)
+    });
+
+    let result = match result {
+        Ok(yak) => {
+            warp::reply::with_status(warp::reply::json(&yak), warp::http::StatusCode::CREATED)
         }
+        Err(err) => error_reply(&err),
+    };
+
+    Ok(result)
+}
+
+/// Renames or re-ages a yak
+///
+/// The change is made today, and recorded in the journal of the shop.
+#[utoipa::path(
+    patch,
+    path = "/admin/herd/{name}",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "The name of the yak")),
+    request_body(content = YakPatchRequest, example = json!({"age": 5})),
+    responses(
+        (status = 200, description = "The yak changed", body = YakResponse),
+        (status = 400, description = "The change is invalid", body = ApiError),
+        (status = 401, description = "The admin token is missing or wrong", body = ApiError),
+        (status = 403, description = "The admin routes are disabled", body = ApiError),
+        (status = 404, description = "There is no such yak", body = ApiError),
+        (status = 500, description = "The herd file can't be written", body = ApiError),
+    ),
+)]
+#[allow(clippy::unused_async)]
+#[allow(clippy::missing_errors_doc)]
+async fn patch_yak(
+    store: Arc<Store>,
+    id: String,
+    name: String,
+    patch: YakPatchRequest,
+) -> Result<impl warp::Reply, Infallible> {
+    let name = percent_decode_str(&name).decode_utf8_lossy();
+
+    let result = store.edit(&id, |journal| {
+        let name = match &patch.name {
+            Some(new_name) => {
+                journal.edit_herd(HerdEdit::Renamed {
+                    name: name.to_string(),
+                    new_name: new_name.clone(),
+                })?;
+                new_name.clone()
+            }
+            None => name.to_string(),
+        };
+        if let Some(age) = patch.age {
+            journal.edit_herd(HerdEdit::Aged {
+                name: name.clone(),
+                age,
+            })?;
+        }
+        herd_yak(journal.shop(), &name)
+    });
+
+    let result = match result {
+        Ok(yak) => warp::reply::with_status(warp::reply::json(&yak), warp::http::StatusCode::OK),
+        Err(err) => error_reply(&err),
+    };
+
+    Ok(result)
+}
+
+/// Retires a yak from the herd
+///
+/// The change is made today, and recorded in the journal of the shop.
+#[utoipa::path(
+    delete,
+    path = "/admin/herd/{name}",
+    security(("admin_token" = [])),
+    params(("name" = String, Path, description = "The name of the yak")),
+    responses(
+        (status = 200, description = "The yak retired", body = YakResponse),
+        (status = 400, description = "The yak can't be retired", body = ApiError),
+        (status = 401, description = "The admin token is missing or wrong", body = ApiError),
+        (status = 403, description = "The admin routes are disabled", body = ApiError),
+        (status = 404, description = "There is no such yak", body = ApiError),
+        (status = 500, description = "The herd file can't be written", body = ApiError),
+    ),
+)]
+#[allow(clippy::unused_async)]
+#[allow(clippy::missing_errors_doc)]
+async fn delete_yak(
+    store: Arc<Store>,
+    id: String,
+    name: String,
+) -> Result<impl warp::Reply, Infallible> {
+    let name = percent_decode_str(&name).decode_utf8_lossy();
+
+    let result = store.edit(&id, |journal| {
+        let yak = herd_yak(journal.shop(), &name)?;
+        journal.edit_herd(HerdEdit::Retired {
+            name: name.to_string(),
+        })?;
+        Ok(yak)
+    });
+
+    let result = match result {
+        Ok(yak) => warp::reply::with_status(warp::reply::json(&yak), warp::http::StatusCode::OK),
+        Err(err) => error_reply(&err),
     };
 
     Ok(result)
 }
+
+/// The yak called `name` in the herd of `shop`, as returned by the admin routes.
+fn herd_yak(shop: &Shop, name: &str) -> Result<YakResponse, YakShopError> {
+    let yak = shop
+        .yak(name)
+        .ok_or_else(|| YakShopError::YakNotFound(name.to_string()))?;
+
+    Ok(YakResponse::new(yak, shop.config().calendar))
+}
+
+/// The stock of the shop on `day` with the number and date of the day. The stock reported is
+/// kept in the database, if there is one.
+fn stock(
+    ledger: &Ledger,
+    day: ShopDay,
+) -> Result<(u32, Products, Option<NaiveDate>), YakShopError> {
+    let (day, stock, date) = ledger.read(day, |journal, day| {
+        let stock = journal.stock(day)?.unwrap_or_default();
+        Ok((day, stock, journal.date(day)))
+    })?;
+
+    ledger.record_stock(day, &stock);
+    Ok((day, stock, date))
+}
+
+/// The profit and loss of the shop up to `day`, with the orders not cancelled by then, and the
+/// date of the day.
+fn profit_and_loss(
+    ledger: &Ledger,
+    day: ShopDay,
+) -> Result<(ProfitAndLoss, Option<NaiveDate>), YakShopError> {
+    ledger.read(day, |journal, day| {
+        let orders: Vec<_> = journal
+            .orders(day)
+            .into_iter()
+            .map(|(_, order)| order)
+            .collect();
+
+        let shop = journal.replay(day)?;
+        Ok((shop.profit_and_loss(&orders)?, shop.date()))
+    })
+}
+
+/// The shop as it was, or will be, on `day`.
+fn replay(ledger: &Ledger, day: ShopDay) -> Result<Shop, YakShopError> {
+    ledger.read(day, |journal, day| journal.replay(day))
+}
+
+/// Replies with the error as an `ApiError`.
+fn error_reply(err: &YakShopError) -> warp::reply::WithStatus<warp::reply::Json> {
+    ApiError::from(err).reply()
+}
+
+/// Runs `work` on a thread where blocking is fine, off the threads serving the requests. Looking
+/// at or ordering for a day a shop hasn't reached steps it through up to 10000 days, and a
+/// simulation runs up to 10000 times.
+async fn blocking<T, E>(work: impl FnOnce() -> Result<T, E> + Send + 'static) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    E: Into<ApiError> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(err) => {
+            eprintln!("Failed to answer the request: {err}");
+            Err(ApiError::new(
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Something went wrong",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn validate(
+        milk: Option<f64>,
+        skins: Option<f64>,
+    ) -> Result<(Option<Quantity>, Option<u32>), &'static str> {
+        OrderItems {
+            milk: milk.map(Liters::Number),
+            skins,
+        }
+        .validate()
+        .map_err(|err| err.field.unwrap())
+    }
+
+    #[test]
+    fn test_validate_order_items() {
+        assert_eq!(
+            validate(Some(1100.5), Some(3.0)),
+            Ok((Some("1100.5".parse().unwrap()), Some(3)))
+        );
+        assert_eq!(validate(None, Some(3.0)), Ok((None, Some(3))));
+
+        let exact = |milk: &str| {
+            OrderItems {
+                milk: Some(Liters::Exact(milk.to_string())),
+                skins: None,
+            }
+            .validate()
+            .map_err(|err| err.field.unwrap())
+        };
+        assert_eq!(
+            exact("1100.500"),
+            Ok((Some("1100.5".parse().unwrap()), None))
+        );
+        assert_eq!(exact("-1"), Err("order.milk"));
+        assert_eq!(exact("a lot"), Err("order.milk"));
+
+        assert_eq!(validate(Some(-1.0), None), Err("order.milk"));
+        assert_eq!(validate(Some(f64::NAN), None), Err("order.milk"));
+        assert_eq!(validate(Some(f64::INFINITY), None), Err("order.milk"));
+        assert_eq!(validate(None, Some(1.5)), Err("order.skins"));
+        assert_eq!(validate(None, Some(-3.0)), Err("order.skins"));
+        assert_eq!(validate(None, None), Err("order"));
+        assert_eq!(validate(Some(0.0), Some(0.0)), Err("order"));
+    }
+
+    #[tokio::test]
+    async fn test_rejections() {
+        let herd = "../tests/fixtures/valid_multi.xml".into();
+        let store = Arc::new(Store::load(herd, None, ShopConfig::default()).unwrap());
+        let routes = routes(&store, None);
+
+        let response = warp::test::request()
+            .path("/yakshop/simulation/13?runs=abc")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 400);
+        assert!(String::from_utf8_lossy(response.body()).contains(r#""code":"bad_request""#));
+
+        // Bodies must state their length, and not be too long
+        let order = || {
+            warp::test::request()
+                .method("POST")
+                .path("/yakshop/order/13")
+                .header("content-type", "application/json")
+        };
+        let response = order().reply(&routes).await;
+        assert_eq!(response.status(), 411);
+        assert!(String::from_utf8_lossy(response.body()).contains(r#""code":"length_required""#));
+
+        let customer = "M".repeat(usize::try_from(MAX_BODY_LENGTH).unwrap());
+        let response = order()
+            .json(&serde_json::json!({"customer": customer, "order": {"skins": 1}}))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 413);
+        assert!(String::from_utf8_lossy(response.body()).contains(r#""code":"payload_too_large""#));
+
+        let response = order()
+            .json(&serde_json::json!({"customer": "Medvedev", "order": {"milk": 1, "skins": 1}}))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 201);
+    }
+
+    /// Looking at later days must not pass them, or orders could no longer be placed on them.
+    #[tokio::test]
+    async fn test_reads_leave_the_journal() {
+        let herd = "../tests/fixtures/valid_multi.xml".into();
+        let store = Arc::new(Store::load(herd, None, ShopConfig::default()).unwrap());
+        let routes = routes(&store, None);
+        let ledger = store.shops().get("valid_multi").unwrap();
+
+        for path in [
+            "/yakshop/stock/13",
+            "/yakshop/herd/13",
+            "/yakshop/events/13",
+            "/yakshop/pnl/13",
+            "/yakshop/cooperative/stock/13",
+            "/yakshop/cooperative/herd/13",
+            "/v2/yakshop/orders/13",
+        ] {
+            let response = warp::test::request().path(path).reply(&routes).await;
+            assert_eq!(response.status(), 200, "{path}");
+            assert_eq!(ledger.journal().day(), 0, "{path}");
+        }
+
+        let response = warp::test::request()
+            .path("/yakshop/stock/4294967295")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 400);
+        assert_eq!(ledger.journal().day(), 0);
+
+        // The order is delivered from the stock of its own day
+        let delivery = ledger
+            .place_order(None, ShopDay::Number(5), "Medvedev", None, Some(3))
+            .unwrap();
+        assert_eq!(delivery.wool, Some(3));
+        assert_eq!(ledger.journal().day(), 5);
+    }
+
+    /// Requests every operation in the `OpenAPI` document from the routes, and checks the routes
+    /// answer with one of the documented statuses, rather than not being there.
+    #[tokio::test]
+    async fn test_openapi_matches_routes() {
+        let dir = std::env::temp_dir().join(format!("yakshop-openapi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let herd = dir.join("herd.xml");
+        fs::copy("../tests/fixtures/valid_multi.xml", &herd).unwrap();
+
+        let store = Store::load(herd, None, ShopConfig::default()).unwrap();
+        let routes = routes(&Arc::new(store), Some(Arc::from("secret")));
+
+        let openapi = openapi::ApiDoc::openapi();
+        assert!(openapi
+            .paths
+            .paths
+            .contains_key("/yakshop/{shop}/stock/{day}"));
+        assert!(openapi
+            .paths
+            .paths
+            .contains_key("/admin/{shop}/herd/{name}"));
+
+        for (path, item) in &openapi.paths.paths {
+            // The stream of updates never ends, see `updates::tests` for it
+            if path.ends_with("/updates") {
+                continue;
+            }
+            let operations = [
+                ("GET", &item.get),
+                ("POST", &item.post),
+                ("PATCH", &item.patch),
+                ("DELETE", &item.delete),
+            ];
+            for (method, operation) in operations {
+                let Some(operation) = operation else {
+                    continue;
+                };
+
+                let uri = path
+                    .replace("{shop}", "herd")
+                    .replace("{day}", "13")
+                    .replace("{name}", "Betty-1")
+                    .replace("{id}", "1");
+                let mut request = warp::test::request()
+                    .method(method)
+                    .path(&uri)
+                    .header("authorization", "Bearer secret");
+                if let Some(body) = &operation.request_body {
+                    let example = body.content["application/json"].example.as_ref();
+                    request = request.json(example.unwrap());
+                }
+                let response = request.reply(&routes).await;
+
+                let status = response.status();
+                let body = String::from_utf8_lossy(response.body());
+                assert!(
+                    operation.responses.responses.contains_key(status.as_str()),
+                    "{method} {uri} answered {status}: {body}"
+                );
+                assert!(
+                    !body.contains(r#""message":"Not found""#),
+                    "{method} {uri} is not routed"
+                );
+            }
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use utoipa::openapi::path::{Operation, ParameterBuilder, ParameterIn, PathItem};
+use utoipa::openapi::schema::{ObjectBuilder, Type};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{self, Required};
+use utoipa::{Modify, OpenApi};
+
+/// The `OpenAPI` document of the routes, served at `/openapi.json`. The routes of the shop served
+/// without a shop id are documented here, `V1Paths` adds the first version of them under `/v1`
+/// and `ShopPaths` adds them all under the name of each shop.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Yakshop",
+        description = "Milk and wool from a herd of yaks. When the shop configuration has a \
+            calendar start date, days can also be given as ISO dates such as `2026-11-01`, and \
+            responses include the matching dates. Errors are returned as an `ApiError`."
+    ),
+    paths(
+        crate::get_stock,
+        crate::get_herd,
+        crate::get_yak,
+        crate::post_order,
+        crate::delete_order,
+        crate::get_events,
+        crate::get_pnl,
+        crate::get_simulation,
+        crate::updates::get_updates,
+        crate::get_cooperative_stock,
+        crate::get_cooperative_herd,
+        crate::post_reload,
+        crate::post_yak,
+        crate::patch_yak,
+        crate::delete_yak,
+        crate::v2::get_stock,
+        crate::v2::get_herd,
+        crate::v2::get_yak,
+        crate::v2::post_order,
+        crate::v2::delete_order,
+        crate::v2::get_orders,
+        crate::v2::get_pnl,
+    ),
+    modifiers(&V1Paths, &ShopPaths, &AdminToken)
+)]
+pub struct ApiDoc;
+
+/// Adds the unversioned routes of the shop again under `/v1`, such as `/v1/yakshop/stock/{day}`
+/// for `/yakshop/stock/{day}`.
+struct V1Paths;
+
+impl Modify for V1Paths {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        let versioned: Vec<_> = openapi
+            .paths
+            .paths
+            .iter()
+            .filter(|(path, _)| path.starts_with("/yakshop/"))
+            .map(|(path, item)| (format!("/v1{path}"), item.clone()))
+            .collect();
+
+        for (path, mut item) in versioned {
+            for operation in operations(&mut item) {
+                operation.operation_id = operation.operation_id.take().map(|id| id + "_v1");
+            }
+            openapi.paths.paths.insert(path, item);
+        }
+    }
+}
+
+/// Adds the routes of every shop under its name, such as `/v2/yakshop/{shop}/stock/{day}` for
+/// `/v2/yakshop/stock/{day}`, which are the only ones served for a directory of herd files.
+struct ShopPaths;
+
+/// The prefixes of the routes served for a shop, followed by its name for a directory of herd
+/// files.
+const SHOP_PREFIXES: [&str; 3] = ["/yakshop/", "/v1/yakshop/", "/v2/yakshop/"];
+
+impl Modify for ShopPaths {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        let shop = ParameterBuilder::new()
+            .name("shop")
+            .parameter_in(ParameterIn::Path)
+            .required(Required::True)
+            .description(Some("The name of the shop, after its herd file"))
+            .schema(Some(ObjectBuilder::new().schema_type(Type::String)))
+            .build();
+
+        let named: Vec<_> = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let named = match SHOP_PREFIXES
+                    .iter()
+                    .find_map(|prefix| Some((prefix, path.strip_prefix(prefix)?)))
+                {
+                    Some((prefix, rest)) if !rest.starts_with(crate::COOPERATIVE) => {
+                        format!("{prefix}{{shop}}/{rest}")
+                    }
+                    Some(_) => return None,
+                    None => format!("/admin/{{shop}}/herd{}", path.strip_prefix("/admin/herd")?),
+                };
+                Some((named, item.clone()))
+            })
+            .collect();
+
+        for (path, mut item) in named {
+            for operation in operations(&mut item) {
+                operation
+                    .parameters
+                    .get_or_insert_with(Vec::new)
+                    .insert(0, shop.clone());
+                operation.operation_id = operation.operation_id.take().map(|id| id + "_of_shop");
+            }
+            openapi.paths.paths.insert(path, item);
+        }
+    }
+}
+
+/// Declares the bearer token of the admin routes.
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+    }
+}
+
+/// The operations of the methods served.
+fn operations(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        &mut item.get,
+        &mut item.post,
+        &mut item.patch,
+        &mut item.delete,
+    ]
+    .into_iter()
+    .flatten()
+}
@@ -0,0 +1,984 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+use yakshop::{
+    Cooperative, Delivery, Event, HerdFormat, Journal, NaiveDate, Order, Products, Quantity, Shop,
+    ShopConfig, ShopDay, YakShopError,
+};
+
+#[cfg(feature = "sqlite")]
+use crate::database::Database;
+
+/// An order placed with an idempotency key, and what was delivered for it. Retries with the same
+/// key get the same delivery, rather than a second one.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    day: u32,
+    customer: String,
+    milk: Option<Quantity>,
+    skins: Option<u32>,
+    delivery: Delivery,
+}
+
+/// How many updates of a shop a subscriber can fall behind on before it misses some.
+const UPDATES_CAPACITY: usize = 256;
+
+/// How many days past today a shop can be looked at or ordered from, so a request for a far-off
+/// day can't keep the server busy stepping the shop forward.
+const MAX_DAYS_AHEAD: u32 = 10_000;
+
+/// The events one change added to the journal of a shop, pushed to the subscribers of its ledger
+/// along with the stock after them.
+#[derive(Debug, Clone)]
+pub struct Update {
+    /// The day the shop has reached
+    pub day: u32,
+    /// The date of `day`, in calendar mode
+    pub date: Option<NaiveDate>,
+    pub events: Vec<Event>,
+    /// The stock on `day`, after the events
+    pub stock: Products,
+}
+
+/// The journal of a shop, shared between the requests. With a database, the decisions in the
+/// journal (orders, cancellations and herd edits) are kept in it along with the receipts of the
+/// orders and the stock reported, so the journal can be replayed after a restart.
+#[derive(Clone)]
+pub struct Ledger {
+    journal: Arc<Mutex<Journal>>,
+    /// The receipts of the orders placed with an idempotency key, by key
+    receipts: Arc<Mutex<BTreeMap<String, Receipt>>>,
+    /// The updates pushed to the subscribers, see `subscribe`
+    updates: broadcast::Sender<Update>,
+    /// The id of the shop and the database its ledger is kept in
+    #[cfg(feature = "sqlite")]
+    database: Option<(String, Arc<Database>)>,
+}
+
+impl Ledger {
+    fn new(origin: Shop) -> Self {
+        Ledger {
+            journal: Arc::new(Mutex::new(Journal::new(origin))),
+            receipts: Arc::default(),
+            updates: broadcast::channel(UPDATES_CAPACITY).0,
+            #[cfg(feature = "sqlite")]
+            database: None,
+        }
+    }
+
+    /// Replays the decisions kept in the database for the shop on `origin`.
+    #[cfg(feature = "sqlite")]
+    fn load(
+        shop: &str,
+        origin: Shop,
+        database: &Arc<Database>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let events = database.journal(shop)?;
+        let receipts = database.receipts(shop)?;
+
+        Ok(Ledger {
+            journal: Arc::new(Mutex::new(Journal::from_events(origin, &events)?)),
+            receipts: Arc::new(Mutex::new(receipts.into_iter().collect())),
+            updates: broadcast::channel(UPDATES_CAPACITY).0,
+            database: Some((shop.to_string(), database.clone())),
+        })
+    }
+
+    pub fn journal(&self) -> MutexGuard<'_, Journal> {
+        self.journal.lock().expect("journal lock poisoned")
+    }
+
+    /// Makes changes to the journal, keeping the decisions made in the database. If they can't be
+    /// kept, the changes are undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns what `update` returns, or `YakShopError::StorageError` if the decisions made
+    /// can't be kept.
+    pub fn update<T>(
+        &self,
+        update: impl FnOnce(&mut Journal) -> Result<T, YakShopError>,
+    ) -> Result<T, YakShopError> {
+        let mut journal = self.journal();
+        let recorded = journal.events().len();
+        let bookmark = journal.bookmark();
+
+        let result = update(&mut journal);
+        if let Err(err) = self.save(&journal, recorded, None) {
+            journal.rewind(bookmark);
+            return Err(err);
+        }
+        self.publish(&journal, recorded);
+
+        result
+    }
+
+    /// Looks at the journal as it is on `day`. A day the shop has not reached yet is looked at on
+    /// a copy of today's shop and stock stepped forward to it, see `Journal::ahead`, so looking
+    /// never changes the journal. The copy is stepped without holding on to the journal, unless
+    /// the journal changed in the meantime. Returns what `read` returns for the number of the day.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if `day` is invalid or more than
+    /// `MAX_DAYS_AHEAD` days ahead, or any error of `read`.
+    pub fn read<T>(
+        &self,
+        day: ShopDay,
+        read: impl FnOnce(JournalView<'_>, u32) -> Result<T, YakShopError>,
+    ) -> Result<T, YakShopError> {
+        let journal = self.journal();
+        let day = journal.shop().day(day)?;
+        if day <= journal.day() {
+            return read(JournalView::new(&journal, None), day);
+        }
+
+        check_days_ahead(&journal, day)?;
+        let seen = (journal.day(), journal.events().len());
+        let mut ahead = journal.ahead();
+        drop(journal);
+        ahead.advance(day)?;
+
+        let journal = self.journal();
+        if (journal.day(), journal.events().len()) != seen {
+            if day <= journal.day() {
+                return read(JournalView::new(&journal, None), day);
+            }
+            ahead = journal.ahead();
+            ahead.advance(day)?;
+        }
+        read(JournalView::new(&journal, Some(ahead)), day)
+    }
+
+    /// Delivers what it can of an order from the stock of `day`, which can't be a day the shop
+    /// has passed. An order with an idempotency `key` which was used before isn't placed again,
+    /// but gets the delivery of the first order with that key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the shop has passed `day`, or the key was used
+    /// for a different order, or `YakShopError::StorageError` if the order can't be kept, in
+    /// which case it isn't placed.
+    pub fn place_order(
+        &self,
+        key: Option<&str>,
+        day: ShopDay,
+        customer: &str,
+        milk: Option<Quantity>,
+        skins: Option<u32>,
+    ) -> Result<Delivery, YakShopError> {
+        // Hold on to the receipts until the order is placed, so a retry waits for the first try
+        let mut receipts = self.receipts.lock().expect("receipts lock poisoned");
+        let mut journal = self.journal();
+
+        let day = journal.shop().day(day)?;
+        if let Some(key) = key {
+            if let Some(receipt) = receipts.get(key) {
+                let same_order = receipt.day == day
+                    && receipt.customer == customer
+                    && (receipt.milk, receipt.skins) == (milk, skins);
+                if !same_order {
+                    return Err(YakShopError::InvalidParameter(format!(
+                        "The idempotency key {key} was already used for a different order"
+                    )));
+                }
+                return Ok(receipt.delivery.clone());
+            }
+        }
+
+        let recorded = journal.events().len();
+        let bookmark = journal.bookmark();
+        let delivery = advance_to_today(&mut journal, day)
+            .map(|()| journal.place_order(customer, milk, skins));
+        let receipt = match (&delivery, key) {
+            (Ok(delivery), Some(key)) => Some((
+                key,
+                Receipt {
+                    day,
+                    customer: customer.to_string(),
+                    milk,
+                    skins,
+                    delivery: delivery.clone(),
+                },
+            )),
+            _ => None,
+        };
+        if let Err(err) = self.save(&journal, recorded, receipt.as_ref()) {
+            journal.rewind(bookmark);
+            return Err(err);
+        }
+        self.publish(&journal, recorded);
+
+        if let Some((key, receipt)) = receipt {
+            receipts.insert(key.to_string(), receipt);
+        }
+        delivery
+    }
+
+    /// Cancels an order on `day`, which can't be a day the shop has passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `YakShopError::InvalidParameter` if the shop has passed `day`, or
+    /// `YakShopError::OrderNotFound` if there is no such order.
+    pub fn cancel_order(&self, day: ShopDay, id: u32) -> Result<Order, YakShopError> {
+        self.update(|journal| {
+            let day = journal.shop().day(day)?;
+            advance_to_today(journal, day)?;
+            journal.cancel_order(id)
+        })
+    }
+
+    /// The updates of the shop from now on, as the journal changes.
+    pub fn subscribe(&self) -> broadcast::Receiver<Update> {
+        self.updates.subscribe()
+    }
+
+    /// Pushes the events since the first `recorded` events of the journal to the subscribers, if
+    /// there are any.
+    fn publish(&self, journal: &Journal, recorded: usize) {
+        let events = &journal.events()[recorded..];
+        if events.is_empty() || self.updates.receiver_count() == 0 {
+            return;
+        }
+
+        let day = journal.day();
+        let update = Update {
+            day,
+            date: journal.shop().config().calendar.date(day),
+            events: events.to_vec(),
+            // SAFETY: The shop has reached `day`, and `advance` checked the stock fits
+            stock: journal.stock(day).ok().flatten().unwrap_or_default(),
+        };
+        // Only fails if the last subscriber left in the meantime
+        self.updates.send(update).ok();
+    }
+
+    /// Keeps the decisions made since the first `recorded` events of the journal in the
+    /// database, if there is one, along with the receipt of the order placed. Failing to keep
+    /// them is logged, and none of them are kept.
+    #[cfg(feature = "sqlite")]
+    fn save(
+        &self,
+        journal: &Journal,
+        recorded: usize,
+        receipt: Option<&(&str, Receipt)>,
+    ) -> Result<(), YakShopError> {
+        let Some((shop, database)) = &self.database else {
+            return Ok(());
+        };
+
+        let decisions: Vec<_> = journal.events()[recorded..]
+            .iter()
+            .filter(|event| event.is_decision())
+            .collect();
+        if decisions.is_empty() && receipt.is_none() {
+            return Ok(());
+        }
+
+        let receipt = receipt.map(|(key, receipt)| (*key, receipt));
+        database
+            .save_events(shop, &decisions, receipt)
+            .map_err(|err| {
+                eprintln!("Failed to store the journal of shop {shop}: {err}");
+                YakShopError::StorageError(format!("the journal of shop {shop}"))
+            })
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    fn save(
+        &self,
+        _journal: &Journal,
+        _recorded: usize,
+        _receipt: Option<&(&str, Receipt)>,
+    ) -> Result<(), YakShopError> {
+        Ok(())
+    }
+
+    /// Keeps a snapshot of the stock reported for `day` in the database, if there is one.
+    #[cfg(feature = "sqlite")]
+    pub fn record_stock(&self, day: u32, stock: &Products) {
+        if let Some((shop, database)) = &self.database {
+            if let Err(err) = database.save_stock(shop, day, stock) {
+                eprintln!("Failed to store the stock of shop {shop}: {err}");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[allow(clippy::unused_self)]
+    pub fn record_stock(&self, _day: u32, _stock: &Products) {}
+}
+
+/// The journal of a shop as it is on a day, see `Ledger::read`.
+pub struct JournalView<'a> {
+    journal: &'a Journal,
+    /// A copy of today's shop stepped forward to the day, if the shop hasn't reached it yet
+    ahead: Option<Journal>,
+}
+
+impl<'a> JournalView<'a> {
+    fn new(journal: &'a Journal, ahead: Option<Journal>) -> Self {
+        JournalView { journal, ahead }
+    }
+
+    /// See `Journal::stock`.
+    pub fn stock(&self, day: u32) -> Result<Option<Products>, YakShopError> {
+        self.ahead.as_ref().unwrap_or(self.journal).stock(day)
+    }
+
+    /// See `Journal::replay`.
+    pub fn replay(&self, day: u32) -> Result<Shop, YakShopError> {
+        self.ahead.as_ref().unwrap_or(self.journal).replay(day)
+    }
+
+    /// See `Journal::orders`. Orders are only placed up to today, so the days ahead have none.
+    pub fn orders(&self, day: u32) -> Vec<(u32, Order)> {
+        self.journal.orders(day)
+    }
+
+    /// See `Journal::events_until`, followed by the events of the days ahead.
+    pub fn events_until(&self, day: u32) -> Vec<&Event> {
+        match &self.ahead {
+            Some(ahead) => self
+                .journal
+                .events()
+                .iter()
+                .chain(ahead.events_until(day))
+                .collect(),
+            None => self.journal.events_until(day).iter().collect(),
+        }
+    }
+
+    /// The date of `day`, in calendar mode.
+    pub fn date(&self, day: u32) -> Option<NaiveDate> {
+        self.journal.shop().config().calendar.date(day)
+    }
+}
+
+/// Steps the journal forward to `day` for orders and cancellations, which can't change the past.
+fn advance_to_today(journal: &mut Journal, day: u32) -> Result<(), YakShopError> {
+    if day < journal.day() {
+        return Err(YakShopError::InvalidParameter(format!(
+            "The shop has already reached day {}, so day {day} can't be changed",
+            journal.day()
+        )));
+    }
+
+    check_days_ahead(journal, day)?;
+    journal.advance(day)
+}
+
+/// Checks `day` is no more than `MAX_DAYS_AHEAD` days past the day the journal has reached.
+fn check_days_ahead(journal: &Journal, day: u32) -> Result<(), YakShopError> {
+    if day.saturating_sub(journal.day()) > MAX_DAYS_AHEAD {
+        return Err(YakShopError::InvalidParameter(format!(
+            "Day {day} is more than {MAX_DAYS_AHEAD} days past day {}, which the shop has reached",
+            journal.day()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether the shops have the same yaks, of the same ages.
+fn same_herd(shop: &Shop, other: &Shop) -> bool {
+    let herd = |shop| HerdFormat::Json.write(shop, true).ok();
+    herd(shop).is_some() && herd(shop) == herd(other)
+}
+
+/// The path segment of the cooperative view, which can't be used as a shop name.
+pub const COOPERATIVE: &str = "cooperative";
+
+/// The shops served as they opened, with the journal of each.
+#[derive(Clone)]
+pub struct Shops {
+    pub cooperative: Cooperative,
+    ledgers: BTreeMap<String, Ledger>,
+    /// The shop served by the routes without a shop id, when a single herd file was given
+    pub default_shop: Option<String>,
+}
+
+impl Shops {
+    /// Loads a single herd file, or every herd file in a directory as a shop of its own.
+    fn load(
+        herd: &Path,
+        format: Option<HerdFormat>,
+        config: &ShopConfig,
+    ) -> Result<Self, YakShopError> {
+        let (cooperative, default_shop) = if herd.is_dir() {
+            (Cooperative::from_dir(herd, config)?, None)
+        } else {
+            let id = herd
+                .file_stem()
+                .map_or_else(|| "herd".into(), |stem| stem.to_string_lossy());
+
+            let mut cooperative = Cooperative::new(config);
+            cooperative.insert(&id, Shop::from_path(herd, format, config)?)?;
+            (cooperative, Some(id.into_owned()))
+        };
+
+        if cooperative.shop(COOPERATIVE).is_some() {
+            return Err(YakShopError::InvalidParameter(format!(
+                "{COOPERATIVE} is reserved for the cooperative view and can't be a shop name"
+            )));
+        }
+
+        let ledgers = cooperative
+            .shops()
+            .iter()
+            .map(|(id, shop)| (id.clone(), Ledger::new(shop.clone())))
+            .collect();
+
+        Ok(Shops {
+            cooperative,
+            ledgers,
+            default_shop,
+        })
+    }
+
+    /// The ledger of the shop with the given id.
+    pub fn get(&self, id: &str) -> Option<Ledger> {
+        self.ledgers.get(id).cloned()
+    }
+
+    /// The ledgers of all shops, by id.
+    pub fn ledgers(&self) -> &BTreeMap<String, Ledger> {
+        &self.ledgers
+    }
+}
+
+/// Holds the shops loaded from the herd file (or directory), and swaps them out as a whole when
+/// the herd is reloaded. Requests keep the shops they started with.
+pub struct Store {
+    herd: PathBuf,
+    format: Option<HerdFormat>,
+    config: ShopConfig,
+    shops: RwLock<Arc<Shops>>,
+    #[cfg(feature = "sqlite")]
+    database: Option<Arc<Database>>,
+}
+
+impl Store {
+    /// Loads the shops from `herd`.
+    pub fn load(
+        herd: PathBuf,
+        format: Option<HerdFormat>,
+        config: ShopConfig,
+    ) -> Result<Self, YakShopError> {
+        let shops = Shops::load(&herd, format, &config)?;
+
+        Ok(Store {
+            herd,
+            format,
+            config,
+            shops: RwLock::new(Arc::new(shops)),
+            #[cfg(feature = "sqlite")]
+            database: None,
+        })
+    }
+
+    /// Keeps the shops in `database` from now on. The shops continue from the journals kept in
+    /// it, and their herds are kept in it whenever they change.
+    #[cfg(feature = "sqlite")]
+    pub fn with_database(mut self, database: Database) -> Result<Self, Box<dyn std::error::Error>> {
+        let database = Arc::new(database);
+
+        let mut shops = Shops::clone(&self.shops());
+        for (id, ledger) in &mut shops.ledgers {
+            let origin = shops.cooperative.shop(id).cloned().unwrap_or_default();
+            *ledger = Ledger::load(id, origin, &database)
+                .map_err(|err| format!("Can't replay the journal of shop {id}: {err}"))?;
+        }
+        self.database = Some(database);
+        self.record_herds(&shops, "loaded");
+
+        self.shops = RwLock::new(Arc::new(shops));
+        Ok(self)
+    }
+
+    /// Keeps the herds of the shops which changed in the database, if there is one, recording
+    /// the change as an event of the given kind.
+    #[cfg(feature = "sqlite")]
+    fn record_herds(&self, shops: &Shops, kind: &str) {
+        let Some(database) = &self.database else {
+            return;
+        };
+
+        for (id, shop) in shops.cooperative.shops() {
+            let herd = match HerdFormat::Json.write(shop, true) {
+                Ok(herd) => herd,
+                Err(err) => {
+                    eprintln!("Failed to store the herd of shop {id}: {err}");
+                    continue;
+                }
+            };
+
+            let detail = format!(r#"{{"yaks":{}}}"#, shop.yaks().len());
+            let result = database.save_herd(id, &herd).and_then(|changed| {
+                if changed {
+                    database.save_event(id, kind, &detail)?;
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                eprintln!("Failed to store the herd of shop {id}: {err}");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[allow(clippy::unused_self)]
+    fn record_herds(&self, _shops: &Shops, _kind: &str) {}
+
+    /// The shops currently served.
+    pub fn shops(&self) -> Arc<Shops> {
+        self.shops.read().expect("shops lock poisoned").clone()
+    }
+
+    /// Loads the herd again and serves it from now on. Shops which are still in the herd replay
+    /// their journal on the new herd, and new shops replay the journal in the database, if there
+    /// is one. If the herd is invalid, or the orders delivered can't be delivered from the new
+    /// herd, the current shops are kept.
+    pub fn reload(&self) -> Result<Arc<Shops>, YakShopError> {
+        let mut shops = Shops::load(&self.herd, self.format, &self.config)?;
+
+        let current = &mut *self.shops.write().expect("shops lock poisoned");
+        // Hold on to the journals until all are replayed, so no order is placed in between
+        let mut rebased = Vec::new();
+        for (id, ledger) in &mut shops.ledgers {
+            let origin = shops.cooperative.shop(id).cloned().unwrap_or_default();
+
+            if let Some(kept) = current.ledgers.get(id) {
+                let journal = kept.journal();
+                // The herd written by an edit is the herd the journal restarted on
+                if same_herd(journal.origin(), &origin) {
+                    *ledger = kept.clone();
+                    continue;
+                }
+
+                let replayed = journal.rebase(origin).map_err(|err| {
+                    YakShopError::InvalidParameter(format!(
+                        "The journal of shop {id} can't be replayed on the new herd: {err}"
+                    ))
+                })?;
+                rebased.push((journal, replayed));
+                *ledger = kept.clone();
+            } else {
+                #[cfg(feature = "sqlite")]
+                if let Some(database) = &self.database {
+                    *ledger = Ledger::load(id, origin, database).map_err(|err| {
+                        YakShopError::InvalidParameter(format!(
+                            "Can't replay the journal of shop {id}: {err}"
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        for (mut journal, replayed) in rebased {
+            *journal = replayed;
+        }
+        self.record_herds(&shops, "reloaded");
+
+        *current = Arc::new(shops);
+        Ok(current.clone())
+    }
+
+    /// Changes the herd of the shop with the given id today, recording the herd edits made by
+    /// `edit` in its journal. Without a database, where the journal doesn't survive a restart,
+    /// the herd as it is after the edits is written back to its herd file, in the format it was
+    /// read in, and the journal restarts on it, see `Journal::restart`. With one, the herd file
+    /// stays as the shop opened, and the edits are replayed on it. The journal is only changed
+    /// once the herd file has been written, so a failed edit leaves both untouched.
+    pub fn edit<T>(
+        &self,
+        id: &str,
+        edit: impl FnOnce(&mut Journal) -> Result<T, YakShopError>,
+    ) -> Result<T, YakShopError> {
+        let ledger = self.shops().get(id).ok_or_else(|| {
+            YakShopError::InvalidParameter(format!("There is no shop named {id}"))
+        })?;
+
+        ledger.update(|journal| {
+            // Edit a copy, so edits which fail halfway leave the journal as it was
+            let mut edited = journal.clone();
+            let result = edit(&mut edited)?;
+
+            if !self.keeps_journals() {
+                self.write_herd(id, edited.shop())?;
+                edited.restart();
+            }
+
+            *journal = edited;
+            Ok(result)
+        })
+    }
+
+    /// Whether the journals are kept in a database, and survive a restart.
+    #[cfg(feature = "sqlite")]
+    fn keeps_journals(&self) -> bool {
+        self.database.is_some()
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[allow(clippy::unused_self)]
+    fn keeps_journals(&self) -> bool {
+        false
+    }
+
+    /// Writes the herd of `shop` to the herd file of the shop with the given id.
+    fn write_herd(&self, id: &str, shop: &Shop) -> Result<(), YakShopError> {
+        let (path, format) = self.herd_file(id)?;
+        let herd = format.write(shop, true)?;
+
+        // Write next to the herd file and move it into place, so the herd file is never half
+        // written when the server stops or the watch reloads it
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, herd)
+            .and_then(|()| std::fs::rename(&tmp, &path))
+            .map_err(YakShopError::ConfigFileWriteError)
+    }
+
+    /// The herd file of the shop with the given id, and the format it is in.
+    fn herd_file(&self, id: &str) -> Result<(PathBuf, HerdFormat), YakShopError> {
+        if self.herd.is_dir() {
+            // The files in a directory are always read in the format of their extension
+            return std::fs::read_dir(&self.herd)
+                .map_err(YakShopError::ConfigFileReadError)?
+                .find_map(|entry| {
+                    let path = entry.ok()?.path();
+                    let format = HerdFormat::from_path(&path)?;
+                    path.file_stem()
+                        .is_some_and(|stem| stem == id)
+                        .then_some((path, format))
+                })
+                .ok_or_else(|| YakShopError::ConfigFileNotFound(self.herd.join(id)));
+        }
+
+        let format = match self.format.or_else(|| HerdFormat::from_path(&self.herd)) {
+            Some(format) => format,
+            None => HerdFormat::detect(
+                &std::fs::read_to_string(&self.herd).map_err(YakShopError::ConfigFileReadError)?,
+            ),
+        };
+
+        Ok((self.herd.clone(), format))
+    }
+
+    /// Steps every shop forward a day whenever a day of `clock` passes, so the subscribers of a
+    /// shop get the shaves, deaths and stock of each day as it passes. Logs the shops which can't
+    /// be stepped forward.
+    pub async fn run_clock(self: Arc<Self>, clock: Clock) {
+        let day_length = clock.day_length();
+        let mut interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + day_length, day_length);
+
+        loop {
+            interval.tick().await;
+
+            for (id, ledger) in self.shops().ledgers() {
+                if let Err(err) = ledger.update(|journal| journal.advance(journal.day() + 1)) {
+                    eprintln!("Failed to step shop {id} to the next day: {err}");
+                }
+            }
+        }
+    }
+
+    /// Reloads the herd whenever the herd file, or a file in the herd directory, changes. Checks
+    /// for changes every `interval`, and logs the outcome of every reload.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut seen = last_modified(&self.herd);
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let modified = last_modified(&self.herd);
+            if modified == seen {
+                continue;
+            }
+            seen = modified;
+
+            match self.reload() {
+                Ok(shops) => println!(
+                    "Reloaded {}, serving {} shop(s)",
+                    self.herd.to_string_lossy(),
+                    shops.cooperative.shops().len()
+                ),
+                Err(err) => eprintln!(
+                    "Failed to reload {}, keeping the current herd: {err}",
+                    self.herd.to_string_lossy()
+                ),
+            }
+        }
+    }
+}
+
+/// How fast the shops pass the days on their own, see `Store::run_clock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// A day passes every day
+    RealTime,
+    /// A day passes every given time
+    Accelerated(Duration),
+}
+
+impl Clock {
+    /// The time a day takes.
+    pub fn day_length(self) -> Duration {
+        match self {
+            Clock::RealTime => Duration::from_secs(24 * 60 * 60),
+            Clock::Accelerated(day_length) => day_length,
+        }
+    }
+}
+
+impl FromStr for Clock {
+    type Err = String;
+
+    /// Parses `real-time`, or the number of seconds a day takes.
+    fn from_str(clock: &str) -> Result<Self, Self::Err> {
+        if clock == "real-time" {
+            return Ok(Clock::RealTime);
+        }
+
+        clock
+            .parse()
+            .ok()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .filter(|day_length| !day_length.is_zero())
+            .map(Clock::Accelerated)
+            .ok_or_else(|| {
+                format!("{clock} is neither real-time nor a positive number of seconds per day")
+            })
+    }
+}
+
+/// The last time `herd` or, for a directory, any file in it was modified. Adding or removing a
+/// file modifies the directory itself.
+fn last_modified(herd: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| {
+        path.metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let files = std::fs::read_dir(herd)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| modified(&entry.ok()?.path()));
+
+    files.chain(modified(herd)).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yakshop::{HerdEdit, Yak};
+
+    fn ledger() -> Ledger {
+        let herd = Path::new("../tests/fixtures/valid_multi.xml");
+        Ledger::new(Shop::from_path(herd, None, &ShopConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn test_idempotent_orders() {
+        let ledger = ledger();
+        let milk = Some("1100".parse().unwrap());
+        let place =
+            |key, customer| ledger.place_order(key, ShopDay::Number(13), customer, milk, None);
+
+        let delivery = place(Some("first"), "Medvedev").unwrap();
+        assert_eq!(delivery.id, Some(1));
+
+        // A retry gets the same delivery, even though the stock is gone
+        assert_eq!(place(Some("first"), "Medvedev").unwrap(), delivery);
+        assert_eq!(place(None, "Medvedev").unwrap().id, None);
+        assert_eq!(ledger.journal().orders(13).len(), 1);
+
+        // A key can't be used for another order
+        assert!(place(Some("first"), "Putin").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_failed_save() {
+        let path = std::env::temp_dir().join(format!("yakshop-save-{}.db", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let database = Arc::new(Database::open(&path).unwrap());
+        let herd = Path::new("../tests/fixtures/valid_multi.xml");
+        let shop = Shop::from_path(herd, None, &ShopConfig::default()).unwrap();
+        let ledger = Ledger::load("herd", shop, &database).unwrap();
+
+        // Make every write of the journal fail
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TRIGGER full BEFORE INSERT ON journal BEGIN SELECT RAISE(FAIL, 'full'); END",
+            )
+            .unwrap();
+
+        let milk = Some("1100".parse().unwrap());
+        let place =
+            || ledger.place_order(Some("first"), ShopDay::Number(13), "Medvedev", milk, None);
+        assert!(matches!(place(), Err(YakShopError::StorageError(_))));
+        assert_eq!(ledger.journal().day(), 0);
+        assert!(ledger.journal().events().is_empty());
+
+        // Neither the order nor its receipt were kept, so a retry places it
+        connection.execute_batch("DROP TRIGGER full").unwrap();
+        assert_eq!(place().unwrap().id, Some(1));
+        assert_eq!(database.journal("herd").unwrap().len(), 1);
+        assert_eq!(database.receipts("herd").unwrap().len(), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let ledger = ledger();
+        let milk = Some("1000".parse().unwrap());
+        ledger
+            .place_order(None, ShopDay::Number(13), "Medvedev", milk, None)
+            .unwrap();
+
+        let read = |day| {
+            ledger
+                .read(ShopDay::Number(day), |journal, day| {
+                    let events = journal.events_until(day).into_iter().cloned().collect();
+                    Ok((journal.stock(day)?, journal.orders(day), events))
+                })
+                .unwrap()
+        };
+        let (stock, orders, events): (_, _, Vec<Event>) = read(20);
+
+        // Looking ahead leaves the journal as it was
+        assert_eq!(ledger.journal().day(), 13);
+
+        let mut journal = ledger.journal().clone();
+        journal.advance(20).unwrap();
+        assert_eq!(stock, journal.stock(20).unwrap());
+        assert_eq!(orders, journal.orders(20));
+        assert_eq!(events, journal.events_until(20));
+    }
+
+    #[test]
+    fn test_updates() {
+        let ledger = ledger();
+        // Nothing is pushed without subscribers
+        ledger.update(|journal| journal.advance(1)).unwrap();
+
+        let mut updates = ledger.subscribe();
+        ledger.update(|journal| journal.advance(3)).unwrap();
+        ledger
+            .place_order(None, ShopDay::Number(3), "Medvedev", None, Some(1))
+            .unwrap();
+        ledger.update(|journal| journal.advance(3)).unwrap();
+
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.day, 3);
+        assert_eq!(update.events.first().map(Event::day), Some(1));
+        assert!(update
+            .events
+            .iter()
+            .all(|event| matches!(event, Event::DayStepped { .. } | Event::Shaved { .. })));
+
+        let update = updates.try_recv().unwrap();
+        assert!(matches!(update.events[..], [Event::OrderDelivered { .. }]));
+        assert_eq!(Some(update.stock), ledger.journal().stock(3).unwrap());
+
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_edit() {
+        let dir = std::env::temp_dir().join(format!("yakshop-edit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let herd = dir.join("herd.xml");
+        std::fs::copy("../tests/fixtures/valid_multi.xml", &herd).unwrap();
+
+        let store = Store::load(herd.clone(), None, ShopConfig::default()).unwrap();
+        let ledger = store.shops().get("herd").unwrap();
+        ledger.update(|journal| journal.advance(13)).unwrap();
+
+        let added = HerdEdit::Added {
+            name: "Dolma".to_string(),
+            age: "2.5".parse().unwrap(),
+            sex: None,
+        };
+        store
+            .edit("herd", |journal| journal.edit_herd(added.clone()))
+            .unwrap();
+
+        // The yak joins today, and the days before are left as they were
+        let journal = ledger.journal();
+        assert_eq!(
+            journal.events().last(),
+            Some(&Event::HerdEdited {
+                day: 13,
+                edit: added
+            })
+        );
+        assert!(journal.shop().yak("Dolma").is_some());
+        assert!(journal.replay(12).unwrap().yak("Dolma").is_none());
+        drop(journal);
+
+        // The herd file has the herd as it is today, which the journal restarts on, so a reload
+        // keeps the journal even once more days have passed
+        assert!(std::fs::read_to_string(&herd).unwrap().contains("Dolma"));
+        ledger.update(|journal| journal.advance(14)).unwrap();
+        store.reload().unwrap();
+        let ages = || {
+            let journal = ledger.journal();
+            let age = |name| journal.shop().yak(name).map(Yak::age_in_days);
+            (journal.day(), age("Betty-1"), age("Dolma"))
+        };
+        assert_eq!(ages(), (14, Some(414), Some(251)));
+
+        // A herd file changed by hand replaces the herd the journal restarted on, without
+        // aging the yaks again
+        let renamed = std::fs::read_to_string(&herd)
+            .unwrap()
+            .replace("Betty-3", "Betty-4");
+        std::fs::write(&herd, renamed).unwrap();
+        store.reload().unwrap();
+        assert_eq!(ages(), (14, Some(414), Some(251)));
+        let journal = ledger.journal();
+        assert!(journal.shop().yak("Betty-4").is_some());
+        assert!(journal.replay(12).unwrap().yak("Betty-3").is_some());
+        drop(journal);
+
+        // A failed edit changes nothing
+        let retired = HerdEdit::Retired {
+            name: "Dolma".to_string(),
+        };
+        assert!(store
+            .edit("herd", |journal| {
+                journal.edit_herd(retired.clone())?;
+                journal.edit_herd(retired.clone())
+            })
+            .is_err());
+        assert!(ledger.journal().shop().yak("Dolma").is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clock() {
+        assert_eq!("real-time".parse(), Ok(Clock::RealTime));
+        assert_eq!(
+            "0.5".parse(),
+            Ok(Clock::Accelerated(Duration::from_millis(500)))
+        );
+        assert!("0".parse::<Clock>().is_err());
+        assert!("-1".parse::<Clock>().is_err());
+        assert!("fast".parse::<Clock>().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! The updates of a shop as server-sent events, so dashboards can follow the shop as it passes
+//! the days and takes orders instead of polling its stock.
+
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::ToSchema;
+use warp::sse;
+
+use yakshop::{Event, NaiveDate, Quantity};
+
+use crate::store::{Ledger, Update};
+
+/// How often an idle stream sends a comment, so proxies don't close it.
+const KEEP_ALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// The data of a `stock` message.
+#[derive(Serialize, ToSchema)]
+struct StockUpdate {
+    /// The day the shop has reached
+    day: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+    #[schema(value_type = f64)]
+    milk: Quantity,
+    wool: u32,
+}
+
+/// The data of a `lagged` message.
+#[derive(Serialize, ToSchema)]
+struct LaggedUpdate {
+    /// The number of updates missed
+    missed: u64,
+}
+
+/// The updates of a shop as they happen
+///
+/// A stream of server-sent events. Every journal event (`day_stepped`, `shaved`, `died`,
+/// `order_delivered`, `order_cancelled` and `herd_edited`) is sent as a message of that name with
+/// the event as its data, followed by a `stock` message with the stock after them. A client
+/// which falls behind gets a `lagged` message with the number of updates it missed. The shop
+/// passes the days on its own when the server runs with `--clock`, and otherwise as orders are
+/// placed or cancelled on later days.
+#[utoipa::path(
+    get,
+    path = "/yakshop/updates",
+    responses(
+        (status = 200, description = "The updates", content_type = "text/event-stream", body = String),
+    ),
+)]
+pub(crate) fn get_updates(ledger: &Ledger) -> impl warp::Reply {
+    let messages = updates(ledger).map(Ok::<_, Infallible>);
+    sse::reply(sse::keep_alive().interval(KEEP_ALIVE).stream(messages))
+}
+
+/// The messages of the updates of the shop from now on.
+fn updates(ledger: &Ledger) -> impl Stream<Item = sse::Event> + Send + 'static {
+    stream::unfold(ledger.subscribe(), |mut updates| async move {
+        let messages = match updates.recv().await {
+            Ok(update) => messages(&update),
+            Err(RecvError::Lagged(missed)) => message("lagged", &LaggedUpdate { missed })
+                .into_iter()
+                .collect(),
+            Err(RecvError::Closed) => return None,
+        };
+
+        Some((stream::iter(messages), updates))
+    })
+    .flatten()
+}
+
+/// A message for every event of the update, followed by the stock.
+fn messages(update: &Update) -> Vec<sse::Event> {
+    let stock = StockUpdate {
+        day: update.day,
+        date: update.date,
+        milk: update.stock.milk(),
+        wool: update.stock.wool(),
+    };
+
+    update
+        .events
+        .iter()
+        .filter_map(|event| message(name(event), event))
+        .chain(message("stock", &stock))
+        .collect()
+}
+
+/// A message with `data` as JSON, or `None` if it can't be serialized, which none of the data
+/// sent can fail to.
+fn message(name: &str, data: &impl Serialize) -> Option<sse::Event> {
+    sse::Event::default().event(name).json_data(data).ok()
+}
+
+/// The name of the message of an event, which is the `event` tag of its JSON.
+fn name(event: &Event) -> &'static str {
+    match event {
+        Event::DayStepped { .. } => "day_stepped",
+        Event::Shaved { .. } => "shaved",
+        Event::Died { .. } => "died",
+        Event::OrderDelivered { .. } => "order_delivered",
+        // Only kept in the database, and delivered when replayed
+        Event::OrderImported { .. } => "order_imported",
+        Event::OrderCancelled { .. } => "order_cancelled",
+        Event::HerdEdited { .. } => "herd_edited",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+    use std::sync::Arc;
+    use yakshop::{ShopConfig, ShopDay};
+
+    #[tokio::test]
+    async fn test_updates() {
+        let herd = "../tests/fixtures/valid_multi.xml".into();
+        let store = Arc::new(Store::load(herd, None, ShopConfig::default()).unwrap());
+        let ledger = store.shops().get("valid_multi").unwrap();
+
+        let updates = updates(&ledger);
+        ledger
+            .place_order(None, ShopDay::Number(1), "Medvedev", None, Some(3))
+            .unwrap();
+
+        let messages: Vec<_> = updates
+            .take(6)
+            .map(|event| event.to_string())
+            .collect()
+            .await;
+        assert_eq!(
+            messages[0],
+            "event:day_stepped\ndata:{\"event\":\"day_stepped\",\"day\":0,\"produced\":{\"milk\":85.5,\"wool\":3}}\n\n"
+        );
+        assert!(messages[1..4]
+            .iter()
+            .all(|message| message.starts_with("event:shaved\n")));
+        assert!(messages[4].starts_with("event:order_delivered\n"));
+        assert_eq!(
+            messages[5],
+            "event:stock\ndata:{\"day\":1,\"milk\":85.5,\"wool\":0}\n\n"
+        );
+    }
+}
@@ -0,0 +1,609 @@
+//! The second version of the shop routes, under `/v2/yakshop`. Amounts are exact decimal strings,
+//! in orders as well as replies, orders have their id, and yaks their sex and next shave day and
+//! date. The routes answer from the same journal as the first version, so only the shape of the
+//! requests and replies differs.
+
+use serde::{Deserialize, Serialize, Serializer};
+use std::convert::Infallible;
+use utoipa::ToSchema;
+use warp::Filter;
+
+use yakshop::{Calendar, NaiveDate, Order, Products, Quantity, Sex, ShopDay, Yak};
+
+use crate::store::Ledger;
+use crate::{blocking, error_reply, place_order, profit_and_loss, replay, stock, ApiError, Liters};
+
+/// An amount as an exact decimal string with three decimals, such as `"1104.480"`, so clients
+/// don't have to round floating point numbers.
+#[derive(Debug, Clone, Copy)]
+struct Amount(Quantity);
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::ProductsResponse)]
+struct ProductsResponse {
+    #[schema(value_type = String, example = "1104.480")]
+    milk: Amount,
+    wool: u32,
+}
+
+impl From<&Products> for ProductsResponse {
+    fn from(products: &Products) -> Self {
+        ProductsResponse {
+            milk: Amount(products.milk()),
+            wool: products.wool(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::StockResponse)]
+struct StockResponse {
+    day: u32,
+    /// The date of the day, or `null` without a calendar start date
+    date: Option<NaiveDate>,
+    #[serde(flatten)]
+    stock: ProductsResponse,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::YakResponse)]
+struct YakResponse {
+    name: String,
+    /// `f` or `m`, or `null` if not known
+    #[schema(value_type = Option<String>)]
+    sex: Option<Sex>,
+    age_days: u32,
+    /// The age in days when the yak was last shaved
+    age_last_shaved_days: u32,
+    alive: bool,
+    /// The day the yak can next be shaved, or `null` if it won't live that long
+    next_shave_day: Option<u32>,
+    /// The date of `next_shave_day`, or `null` without a calendar start date
+    next_shave_date: Option<NaiveDate>,
+}
+
+impl YakResponse {
+    fn new(yak: &Yak, calendar: Calendar) -> Self {
+        let next_shave_day = yak.next_shave_shop_day();
+        YakResponse {
+            name: yak.name().to_string(),
+            sex: yak.sex(),
+            age_days: yak.age_in_days(),
+            age_last_shaved_days: yak.age_last_shaved_in_days(),
+            alive: yak.is_alive(),
+            next_shave_day,
+            next_shave_date: next_shave_day.and_then(|day| calendar.date(day)),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::YakDetailResponse)]
+struct YakDetailResponse {
+    #[serde(flatten)]
+    yak: YakResponse,
+    /// The products produced by the yak since the shop opened
+    produced: ProductsResponse,
+    shave_days: Vec<u32>,
+    days_remaining: u32,
+    /// The products the yak will produce for the rest of its life
+    projected: ProductsResponse,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::HerdResponse)]
+struct HerdResponse {
+    day: u32,
+    date: Option<NaiveDate>,
+    herd: Vec<YakResponse>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::OrderRequest)]
+pub(crate) struct OrderRequest {
+    customer: String,
+    order: OrderItems,
+}
+
+/// The products ordered, with the milk as an exact decimal string like the replies.
+#[derive(Deserialize, ToSchema)]
+#[schema(as = v2::OrderItems)]
+struct OrderItems {
+    /// In liters, as a decimal string such as `"1100.500"`; a plain number is taken too
+    #[schema(value_type = Option<String>, example = "1100.500")]
+    milk: Option<Liters>,
+    skins: Option<f64>,
+}
+
+impl From<OrderRequest> for crate::OrderRequest {
+    fn from(request: OrderRequest) -> Self {
+        crate::OrderRequest {
+            customer: request.customer,
+            order: crate::OrderItems {
+                milk: request.order.milk,
+                skins: request.order.skins,
+            },
+        }
+    }
+}
+
+/// The products delivered for an order, and the id it was recorded under.
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::DeliveryResponse)]
+struct DeliveryResponse {
+    id: u32,
+    /// Left out if no milk was delivered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "1100.000")]
+    milk: Option<Amount>,
+    /// Left out if no skins were delivered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skins: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::OrderResponse)]
+struct OrderResponse {
+    id: u32,
+    customer: String,
+    /// The day the order was delivered on
+    day: u32,
+    #[schema(value_type = String, example = "1100.000")]
+    milk: Amount,
+    skins: u32,
+}
+
+impl OrderResponse {
+    fn new(id: u32, order: &Order) -> Self {
+        OrderResponse {
+            id,
+            customer: order.customer.clone(),
+            day: order.day,
+            milk: Amount(order.delivered.milk()),
+            skins: order.delivered.wool(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::OrdersResponse)]
+struct OrdersResponse {
+    day: u32,
+    date: Option<NaiveDate>,
+    /// The orders delivered and not cancelled by the day, oldest first
+    orders: Vec<OrderResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::CostsResponse)]
+struct CostsResponse {
+    #[schema(value_type = String)]
+    feed: Amount,
+    #[schema(value_type = String)]
+    care: Amount,
+    #[schema(value_type = String)]
+    total: Amount,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = v2::ProfitAndLossResponse)]
+struct ProfitAndLossResponse {
+    date: Option<NaiveDate>,
+    /// The products sold by the orders delivered up to the day
+    sold: ProductsResponse,
+    #[schema(value_type = String)]
+    revenue: Amount,
+    costs: CostsResponse,
+    /// The revenue less the costs, starting with `-` for a loss
+    #[schema(example = "-163.570")]
+    profit: String,
+}
+
+/// The routes of a single shop, picked by `shop`.
+pub(crate) fn shop_routes<F>(
+    shop: F,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (Ledger,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+{
+    let stock = shop
+        .clone()
+        .and(warp::path!("stock" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_stock(days, ledger));
+
+    let herd = shop
+        .clone()
+        .and(warp::path!("herd" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_herd(days, ledger));
+
+    let yak = shop
+        .clone()
+        .and(warp::path!("herd" / ShopDay / String))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay, name: String| get_yak(days, name, ledger));
+
+    let order = shop
+        .clone()
+        .and(warp::path!("order" / ShopDay))
+        .and(warp::post())
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(crate::json_body())
+        .and_then(
+            |ledger: Ledger, days: ShopDay, key: Option<String>, order: OrderRequest| {
+                post_order(days, key, order, ledger)
+            },
+        );
+
+    let cancel = shop
+        .clone()
+        .and(warp::path!("order" / ShopDay / u32))
+        .and(warp::delete())
+        .and_then(|ledger: Ledger, days: ShopDay, id: u32| delete_order(days, id, ledger));
+
+    let orders = shop
+        .clone()
+        .and(warp::path!("orders" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_orders(days, ledger));
+
+    let pnl = shop
+        .and(warp::path!("pnl" / ShopDay))
+        .and(warp::get())
+        .and_then(|ledger: Ledger, days: ShopDay| get_pnl(days, ledger));
+
+    stock
+        .or(herd)
+        .or(yak)
+        .or(order)
+        .or(cancel)
+        .or(orders)
+        .or(pnl)
+}
+
+/// The stock of the shop on a day
+///
+/// The milk and wool produced up to the day, less the orders delivered by then.
+#[utoipa::path(
+    get,
+    path = "/v2/yakshop/stock/{day}",
+    operation_id = "get_stock_v2",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The stock", body = StockResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 422, description = "The stock overflowed", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn get_stock(
+    day: ShopDay,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let result = match blocking(move || stock(&ledger, day)).await {
+        Ok((day, stock, date)) => warp::reply::with_status(
+            warp::reply::json(&StockResponse {
+                day,
+                date,
+                stock: ProductsResponse::from(&stock),
+            }),
+            warp::http::StatusCode::OK,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// The herd of the shop on a day
+#[utoipa::path(
+    get,
+    path = "/v2/yakshop/herd/{day}",
+    operation_id = "get_herd_v2",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The yaks, including the dead ones", body = HerdResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn get_herd(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let shop = match blocking(move || replay(&ledger, day)).await {
+        Ok(shop) => shop,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let calendar = shop.config().calendar;
+    let yaks = shop
+        .yaks()
+        .iter()
+        .map(|yak| YakResponse::new(yak, calendar));
+    Ok(warp::reply::with_status(
+        warp::reply::json(&HerdResponse {
+            day: shop.elapsed_days,
+            date: shop.date(),
+            herd: yaks.collect(),
+        }),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+/// A yak of the shop on a day
+///
+/// The yak's lifetime production, shave days, days remaining and projected production for the
+/// rest of its life.
+#[utoipa::path(
+    get,
+    path = "/v2/yakshop/herd/{day}/{name}",
+    operation_id = "get_yak_v2",
+    params(
+        ("day" = String, Path, description = "The day number, or an ISO date in calendar mode"),
+        ("name" = String, Path, description = "The name of the yak"),
+    ),
+    responses(
+        (status = 200, description = "The yak", body = YakDetailResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 404, description = "There is no such yak", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn get_yak(
+    day: ShopDay,
+    name: String,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let shop = match blocking(move || replay(&ledger, day)).await {
+        Ok(shop) => shop,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let name = percent_encoding::percent_decode_str(&name).decode_utf8_lossy();
+
+    let result = match shop.yak(&name) {
+        Some(yak) => warp::reply::with_status(
+            warp::reply::json(&YakDetailResponse {
+                yak: YakResponse::new(yak, shop.config().calendar),
+                produced: ProductsResponse::from(yak.produced_products()),
+                shave_days: yak.shave_days().to_vec(),
+                days_remaining: yak.days_remaining(),
+                projected: ProductsResponse::from(&yak.projected_products()),
+            }),
+            warp::http::StatusCode::OK,
+        ),
+        None => error_reply(&yakshop::YakShopError::YakNotFound(name.into_owned())),
+    };
+
+    Ok(result)
+}
+
+/// Places an order on a day
+///
+/// Each product is delivered in full or not at all, and taken from the stock. Orders can't be
+/// placed on a day the shop has passed.
+#[utoipa::path(
+    post,
+    path = "/v2/yakshop/order/{day}",
+    operation_id = "post_order_v2",
+    params(
+        ("day" = String, Path, description = "The day number, or an ISO date in calendar mode"),
+        (
+            "Idempotency-Key" = Option<String>,
+            Header,
+            description = "A unique key for the order, so retries with it aren't delivered twice"
+        ),
+    ),
+    request_body(
+        content = OrderRequest,
+        example = json!({"customer": "Medvedev", "order": {"milk": "1100.500", "skins": 3}}),
+    ),
+    responses(
+        (status = 201, description = "The order was delivered in full", body = DeliveryResponse),
+        (status = 206, description = "Only the milk or the skins were delivered", body = DeliveryResponse),
+        (status = 400, description = "The order or day is invalid", body = ApiError),
+        (status = 409, description = "None of the order is in stock", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn post_order(
+    day: ShopDay,
+    key: Option<String>,
+    order: OrderRequest,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let delivery = blocking(move || place_order(day, key.as_deref(), &order.into(), &ledger)).await;
+    let result = match delivery {
+        Ok((delivery, status)) => warp::reply::with_status(
+            warp::reply::json(&DeliveryResponse {
+                // Only orders which delivered something get here, and those have an id
+                id: delivery.id.unwrap_or_default(),
+                milk: delivery.milk.map(Amount),
+                skins: delivery.wool,
+            }),
+            status,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// Cancels an order on a day
+///
+/// The products of the order go back to the stock.
+#[utoipa::path(
+    delete,
+    path = "/v2/yakshop/order/{day}/{id}",
+    operation_id = "delete_order_v2",
+    params(
+        ("day" = String, Path, description = "The day number, or an ISO date in calendar mode"),
+        ("id" = u32, Path, description = "The id of the order"),
+    ),
+    responses(
+        (status = 200, description = "The order cancelled", body = OrderResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 404, description = "There is no such order", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn delete_order(
+    day: ShopDay,
+    id: u32,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let result = match blocking(move || ledger.cancel_order(day, id)).await {
+        Ok(order) => warp::reply::with_status(
+            warp::reply::json(&OrderResponse::new(id, &order)),
+            warp::http::StatusCode::OK,
+        ),
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// The orders of the shop up to a day
+#[utoipa::path(
+    get,
+    path = "/v2/yakshop/orders/{day}",
+    operation_id = "get_orders_v2",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The orders", body = OrdersResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn get_orders(
+    day: ShopDay,
+    ledger: Ledger,
+) -> Result<impl warp::Reply, Infallible> {
+    let orders = blocking(move || {
+        ledger.read(day, |journal, day| {
+            Ok(OrdersResponse {
+                day,
+                date: journal.date(day),
+                orders: journal
+                    .orders(day)
+                    .iter()
+                    .map(|(id, order)| OrderResponse::new(*id, order))
+                    .collect(),
+            })
+        })
+    })
+    .await;
+
+    let result = match orders {
+        Ok(orders) => {
+            warp::reply::with_status(warp::reply::json(&orders), warp::http::StatusCode::OK)
+        }
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// The profit and loss of the shop up to a day
+///
+/// The revenue of the orders delivered up to the day, the feed and care costs of the herd and the
+/// profit.
+#[utoipa::path(
+    get,
+    path = "/v2/yakshop/pnl/{day}",
+    operation_id = "get_pnl_v2",
+    params(("day" = String, Path, description = "The day number, or an ISO date in calendar mode")),
+    responses(
+        (status = 200, description = "The profit and loss", body = ProfitAndLossResponse),
+        (status = 400, description = "The day is invalid", body = ApiError),
+        (status = 422, description = "The totals overflowed", body = ApiError),
+    ),
+)]
+#[allow(clippy::missing_errors_doc)]
+pub(crate) async fn get_pnl(day: ShopDay, ledger: Ledger) -> Result<impl warp::Reply, Infallible> {
+    let result = match blocking(move || profit_and_loss(&ledger, day)).await {
+        Ok((pnl, date)) => {
+            let costs = pnl.costs();
+            warp::reply::with_status(
+                warp::reply::json(&ProfitAndLossResponse {
+                    date,
+                    sold: ProductsResponse::from(&pnl.sold),
+                    revenue: Amount(pnl.revenue),
+                    costs: CostsResponse {
+                        feed: Amount(pnl.expenses.feed),
+                        care: Amount(pnl.expenses.care),
+                        total: Amount(costs),
+                    },
+                    profit: difference(pnl.revenue, costs),
+                }),
+                warp::http::StatusCode::OK,
+            )
+        }
+        Err(err) => err.reply(),
+    };
+
+    Ok(result)
+}
+
+/// `a - b` as an exact decimal string, starting with `-` if it is negative.
+fn difference(a: Quantity, b: Quantity) -> String {
+    if a >= b {
+        (a - b).to_string()
+    } else {
+        format!("-{}", b - a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amounts() {
+        let milk = Quantity::try_from_f64(1104.48).unwrap();
+        let json = serde_json::to_string(&ProductsResponse::from(&Products::new(milk, 3))).unwrap();
+        assert_eq!(json, r#"{"milk":"1104.480","wool":3}"#);
+
+        let revenue = Quantity::try_from_f64(160.6).unwrap();
+        let costs = Quantity::try_from_f64(324.17).unwrap();
+        assert_eq!(difference(revenue, costs), "-163.570");
+        assert_eq!(difference(costs, revenue), "163.570");
+        assert_eq!(difference(costs, costs), "0.000");
+    }
+
+    #[tokio::test]
+    async fn test_order_amounts() {
+        let herd = "../tests/fixtures/valid_multi.xml".into();
+        let config = yakshop::ShopConfig::default();
+        let store = std::sync::Arc::new(crate::store::Store::load(herd, None, config).unwrap());
+        let routes = crate::routes(&store, None);
+
+        let order = |milk: serde_json::Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/v2/yakshop/order/13")
+                .json(&serde_json::json!({"customer": "Medvedev", "order": {"milk": milk, "skins": 1}}))
+                .reply(&routes)
+        };
+
+        let response = order(serde_json::json!("100.125")).await;
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.body(), r#"{"id":1,"milk":"100.125","skins":1}"#);
+
+        let response = order(serde_json::json!(10.5)).await;
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.body(), r#"{"id":2,"milk":"10.500","skins":1}"#);
+
+        for milk in ["-1", "a lot", ""] {
+            let response = order(serde_json::json!(milk)).await;
+            assert_eq!(response.status(), 400, "{milk}");
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["field"], "order.milk", "{milk}");
+        }
+    }
+}